@@ -0,0 +1,6 @@
+-- Add migration script here
+create table if not exists scheduler_skipped_ticks (
+    id serial primary key,
+    skipped_at timestamp with time zone not null default current_timestamp,
+    checks_in_flight int not null
+);
@@ -0,0 +1,13 @@
+-- Add migration script here
+alter table websites
+    add column if not exists check_interval_secs int not null default 60,
+    add column if not exists timeout_secs int not null default 30,
+    add column if not exists retries int not null default 0;
+
+-- websites can now be checked more than once a minute, so logs
+-- need their exact timestamp and can no longer be unique per minute
+alter table logs alter column created_at set default current_timestamp;
+alter table logs drop constraint if exists logs_website_id_created_at_key;
+
+alter table scheduler_skipped_ticks
+    add column if not exists website_id int references websites(id) on delete cascade;
@@ -0,0 +1,4 @@
+-- Add migration script here
+alter table logs
+    add column if not exists error_kind varchar(32),
+    add column if not exists error_message varchar;
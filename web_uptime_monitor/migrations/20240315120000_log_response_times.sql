@@ -0,0 +1,4 @@
+-- Add migration script here
+alter table logs
+    add column if not exists response_time_ms int,
+    add column if not exists ttfb_ms int;
@@ -0,0 +1,9 @@
+-- Add migration script here
+alter table websites
+    add column if not exists accepted_statuses varchar not null default '200-299';
+
+-- whether a check counted as up is decided by the checker using the
+-- website's rule at the time, so the stats don't have to re-evaluate it
+alter table logs add column if not exists is_up boolean;
+update logs set is_up = coalesce(status = 200, false) and error_kind is null where is_up is null;
+alter table logs alter column is_up set not null;
@@ -0,0 +1,9 @@
+-- Add migration script here
+alter table websites
+    add column if not exists body_contains varchar,
+    add column if not exists body_not_contains varchar,
+    add column if not exists body_regex varchar,
+    add column if not exists min_body_bytes int,
+    add column if not exists max_body_bytes int;
+
+alter table logs add column if not exists failed_assertion varchar;
@@ -0,0 +1,4 @@
+-- Add migration script here
+alter table websites add column if not exists json_assertions text;
+
+alter table logs add column if not exists json_values jsonb;
@@ -0,0 +1,8 @@
+-- Add migration script here
+alter table websites
+    add column if not exists http_method varchar(10) not null default 'GET',
+    add column if not exists request_headers bytea,
+    add column if not exists request_body text,
+    add column if not exists auth_kind varchar(10) not null default 'none',
+    add column if not exists auth_username varchar,
+    add column if not exists auth_secret bytea;
@@ -0,0 +1,13 @@
+-- Add migration script here
+alter table websites
+    add column if not exists cert_expiry_days int not null default 14;
+
+create table if not exists certificates (
+    website_id int primary key references websites(id) on delete cascade,
+    subject varchar not null,
+    issuer varchar not null,
+    sans varchar[] not null,
+    not_after timestamp with time zone not null,
+    chain_valid boolean not null,
+    checked_at timestamp with time zone not null default current_timestamp
+);
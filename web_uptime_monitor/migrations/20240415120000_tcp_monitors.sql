@@ -0,0 +1,5 @@
+-- Add migration script here
+alter table websites
+    add column if not exists kind varchar(16) not null default 'http',
+    add column if not exists tcp_send varchar,
+    add column if not exists tcp_expect varchar;
@@ -0,0 +1,7 @@
+-- Add migration script here
+alter table websites
+    add column if not exists dns_record_type varchar(8) not null default 'A',
+    add column if not exists dns_resolver varchar,
+    add column if not exists dns_expected varchar;
+
+alter table logs add column if not exists dns_answers varchar[];
@@ -0,0 +1,15 @@
+-- Add migration script here
+alter table websites
+    add column if not exists heartbeat_token varchar(64) unique,
+    add column if not exists heartbeat_grace_secs int not null default 60,
+    add column if not exists created_at timestamp with time zone not null default current_timestamp;
+
+create table if not exists heartbeats (
+    id serial primary key,
+    website_id int not null references websites(id) on delete cascade,
+    kind varchar(8) not null,
+    duration_ms int,
+    received_at timestamp with time zone not null default current_timestamp
+);
+
+create index if not exists heartbeats_website_id_received_at on heartbeats (website_id, received_at);
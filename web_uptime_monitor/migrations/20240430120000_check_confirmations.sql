@@ -0,0 +1,18 @@
+-- Add migration script here
+alter table websites
+    add column if not exists confirm_failures int;
+
+create table if not exists check_attempts (
+    id serial primary key,
+    log_id int not null references logs(id) on delete cascade,
+    attempt int not null,
+    is_up boolean not null,
+    status smallint,
+    error_kind varchar(32),
+    error_message varchar,
+    failed_assertion varchar,
+    response_time_ms int,
+    created_at timestamp with time zone not null default current_timestamp
+);
+
+create index if not exists check_attempts_log_id on check_attempts (log_id);
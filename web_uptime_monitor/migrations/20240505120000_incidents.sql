@@ -0,0 +1,34 @@
+-- Add migration script here
+create table if not exists incidents (
+    id serial primary key,
+    website_id int not null references websites(id) on delete cascade,
+    opened_at timestamp with time zone not null default current_timestamp,
+    resolved_at timestamp with time zone,
+    cause varchar not null,
+    first_status smallint,
+    last_status smallint,
+    failed_checks int not null default 1
+);
+
+-- a website has at most one open incident
+create unique index if not exists incidents_open_website_id on incidents (website_id) where resolved_at is null;
+create index if not exists incidents_website_id_opened_at on incidents (website_id, opened_at);
+
+-- group the failed checks logged so far into incidents, every run of
+-- failures between two successful checks of a website is one incident
+insert into incidents (website_id, opened_at, resolved_at, cause, first_status, last_status, failed_checks)
+select website_id,
+    min(created_at),
+    (select min(up.created_at) from logs up
+        where up.website_id = failures.website_id and up.is_up and up.created_at > max(failures.created_at)),
+    (array_agg(coalesce(error_kind || ': ' || error_message, failed_assertion, 'status ' || status, 'unknown failure')
+        order by created_at))[1],
+    (array_agg(status order by created_at))[1],
+    (array_agg(status order by created_at desc))[1],
+    count(*)
+from (
+    select *, count(*) filter (where is_up) over (partition by website_id order by created_at) as run
+    from logs
+) failures
+where not is_up and not exists (select 1 from incidents)
+group by website_id, run;
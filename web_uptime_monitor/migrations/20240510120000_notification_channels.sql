@@ -0,0 +1,32 @@
+-- Add migration script here
+create table if not exists notification_channels (
+    id serial primary key,
+    name varchar(75) not null unique,
+    kind varchar(16) not null,
+    url varchar not null,
+    secret bytea,
+    created_at timestamp with time zone not null default current_timestamp
+);
+
+create table if not exists website_channels (
+    website_id int not null references websites(id) on delete cascade,
+    channel_id int not null references notification_channels(id) on delete cascade,
+    primary key (website_id, channel_id)
+);
+
+create table if not exists notification_deliveries (
+    id serial primary key,
+    channel_id int not null references notification_channels(id) on delete cascade,
+    website_id int not null references websites(id) on delete cascade,
+    incident_id int references incidents(id) on delete set null,
+    event varchar(16) not null,
+    payload jsonb not null,
+    status varchar(16) not null default 'pending',
+    attempts int not null default 0,
+    response_status smallint,
+    error varchar,
+    created_at timestamp with time zone not null default current_timestamp,
+    delivered_at timestamp with time zone
+);
+
+create index if not exists notification_deliveries_created_at on notification_deliveries (created_at);
@@ -0,0 +1,19 @@
+-- Add migration script here
+alter table incidents add column if not exists acknowledged_at timestamp with time zone;
+
+alter table website_channels add column if not exists escalate_after_mins int not null default 0;
+
+create table if not exists incident_escalations (
+    incident_id int not null references incidents(id) on delete cascade,
+    channel_id int not null references notification_channels(id) on delete cascade,
+    escalated_at timestamp with time zone not null default current_timestamp,
+    primary key (incident_id, channel_id)
+);
+
+-- incidents that are already open have been sent to every channel of their website
+insert into incident_escalations (incident_id, channel_id)
+select incidents.id, website_channels.channel_id
+from incidents
+join website_channels on website_channels.website_id = incidents.website_id
+where incidents.resolved_at is null
+on conflict do nothing;
@@ -0,0 +1,2 @@
+-- Add migration script here
+alter table websites add column if not exists flapping_since timestamp with time zone;
@@ -0,0 +1,26 @@
+-- Add migration script here
+create table if not exists maintenance_windows (
+    id serial primary key,
+    name varchar(75) not null,
+    -- one-off windows
+    starts_at timestamp with time zone,
+    ends_at timestamp with time zone,
+    -- recurring windows
+    cron varchar,
+    duration_mins int,
+    created_at timestamp with time zone not null default current_timestamp,
+    check (
+        (starts_at is not null and ends_at > starts_at and cron is null)
+        or (cron is not null and duration_mins > 0 and starts_at is null and ends_at is null)
+    )
+);
+
+-- a window without any monitors covers all of them
+create table if not exists maintenance_window_websites (
+    window_id int not null references maintenance_windows(id) on delete cascade,
+    website_id int not null references websites(id) on delete cascade,
+    primary key (window_id, website_id)
+);
+
+-- checks run during maintenance are kept but don't count against uptime
+alter table logs add column if not exists in_maintenance boolean not null default false;
@@ -0,0 +1,12 @@
+-- Add migration script here
+alter table websites add column if not exists paused_at timestamp with time zone;
+
+-- every time a monitor was paused, so the uptime bars can tell paused time from missing data
+create table if not exists website_pauses (
+    id serial primary key,
+    website_id int not null references websites(id) on delete cascade,
+    paused_at timestamp with time zone not null default current_timestamp,
+    resumed_at timestamp with time zone
+);
+
+create index if not exists website_pauses_website_id_idx on website_pauses (website_id, paused_at);
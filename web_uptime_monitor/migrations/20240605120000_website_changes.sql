@@ -0,0 +1,12 @@
+-- Add migration script here
+-- one row per setting that changed when a monitor was edited
+create table if not exists website_changes (
+    id serial primary key,
+    website_id int not null references websites(id) on delete cascade,
+    changed_at timestamp with time zone not null default current_timestamp,
+    field varchar(32) not null,
+    old_value varchar,
+    new_value varchar
+);
+
+create index if not exists website_changes_website_id_idx on website_changes (website_id, changed_at);
@@ -0,0 +1,12 @@
+-- Add migration script here
+alter table incidents add column if not exists kind varchar not null default 'down';
+
+-- certificates about to expire used to be recorded as the website going down,
+-- they're their own kind of incident now and don't count against uptime
+update incidents set kind = 'certificate' where cause like 'certificate_expiring: %';
+update incidents set cause = substr(cause, length('certificate_expiring: ') + 1) where kind = 'certificate';
+update logs set is_up = true, error_kind = null, error_message = null where error_kind = 'certificate_expiring';
+
+-- a website has at most one open incident of each kind
+drop index if exists incidents_open_website_id;
+create unique index if not exists incidents_open_website_id_kind on incidents (website_id, kind) where resolved_at is null;
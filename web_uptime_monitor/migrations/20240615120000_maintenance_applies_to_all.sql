@@ -0,0 +1,7 @@
+-- Add migration script here
+-- windows used to cover every monitor when they had none left, so deleting the
+-- last monitor of a window turned it into one for every monitor
+alter table maintenance_windows add column if not exists applies_to_all boolean not null default false;
+
+update maintenance_windows set applies_to_all = true
+where not exists (select 1 from maintenance_window_websites where window_id = maintenance_windows.id);
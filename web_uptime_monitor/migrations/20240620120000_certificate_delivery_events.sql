@@ -0,0 +1,3 @@
+-- Add migration script here
+-- certificate_expiring and certificate_renewed don't fit the old limit
+alter table notification_deliveries alter column event type varchar;
@@ -0,0 +1,256 @@
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use axum::extract::{Json, Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::certificates::Certificate;
+use crate::incidents::{Incident, IncidentKind};
+use crate::{
+    get_daily_stats, get_monthly_stats, insert_website, remove_website, save_website, ApiError, AppState, ErrorBody,
+    MonitorKind, Website, WebsiteForm, WebsiteStats,
+};
+
+/*
+/ the OpenAPI 3 document of the api, generated from the
+/ handlers and types below. it's served at
+/ /api/v1/openapi.json with interactive docs at /api/docs
+ */
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Shuttle Status Monitor", description = "Uptime monitoring of websites, tcp and dns services and cron jobs"),
+    paths(list_monitors, create_monitor, get_monitor, update_monitor, delete_monitor, get_logs),
+    components(schemas(
+        Website, WebsiteForm, MonitorKind, MonitorDetails, WebsiteStats, Incident, IncidentKind, Certificate, CheckLog,
+        ErrorBody,
+    )),
+    tags((name = "monitors", description = "Monitors and their checks, addressed by alias"))
+)]
+pub struct ApiDoc;
+
+/*
+/ the json api, mounted at /api/v1 so the html pages can
+/ change without breaking whatever is built on top of it.
+/ monitors are addressed by alias like on the pages, and
+/ created or replaced with the same settings as the form.
+/ there's no PATCH, a monitor is always updated as a whole
+ */
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/monitors", get(list_monitors).post(create_monitor))
+        .route("/monitors/:alias", get(get_monitor).put(update_monitor).delete(delete_monitor))
+        .route("/monitors/:alias/logs", get(get_logs))
+}
+
+/*
+/ a monitor with everything its page shows
+ */
+#[derive(Serialize, ToSchema)]
+struct MonitorDetails {
+    #[serde(flatten)]
+    monitor: Website,
+    certificate: Option<Certificate>,
+    daily_stats: Vec<WebsiteStats>,
+    monthly_stats: Vec<WebsiteStats>,
+    incidents: Vec<Incident>,
+}
+
+/*
+/ a single check as it was stored in the logs table
+ */
+#[derive(sqlx::FromRow, Serialize, ToSchema)]
+struct CheckLog {
+    id: i32,
+    created_at: DateTime<Utc>,
+    is_up: bool,
+    status: Option<i16>,
+    error_kind: Option<String>,
+    error_message: Option<String>,
+    failed_assertion: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    json_values: Option<Value>,
+    dns_answers: Option<Vec<String>>,
+    /// total time of the check, dns lookup, connect and tls handshake aren't recorded separately
+    response_time_ms: Option<i32>,
+    /// time until the response headers arrived, or until a tcp monitor connected
+    ttfb_ms: Option<i32>,
+    in_maintenance: bool,
+}
+
+/*
+/ logs are returned newest first, pass the created_at of the
+/ last one as before to get the page after it
+ */
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct LogsQuery {
+    /// how many logs to return, at most 1000
+    #[serde(default = "default_logs_limit")]
+    #[param(default = 100, minimum = 1, maximum = 1000)]
+    limit: i64,
+    /// only return logs created before this time
+    before: Option<DateTime<Utc>>,
+}
+
+fn default_logs_limit() -> i64 {
+    100
+}
+
+/*
+/ malformed bodies and query strings get the same json
+/ errors as everything else instead of axum's plain text
+ */
+fn json_body<T>(body: Result<Json<T>, JsonRejection>) -> Result<T, ApiError> {
+    body.map(|Json(body)| body).map_err(|e| ApiError::Validation(e.body_text()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/monitors",
+    tag = "monitors",
+    responses(
+        (status = 200, description = "Every monitor", body = [Website]),
+        (status = 500, description = "The database failed", body = ErrorBody),
+    )
+)]
+async fn list_monitors(State(state): State<AppState>) -> Result<Json<Vec<Website>>, ApiError> {
+    let websites = sqlx::query_as::<_, Website>("SELECT * FROM websites ORDER BY alias")
+        .fetch_all(&state.db)
+        .await?;
+
+    Ok(Json(websites))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/monitors",
+    tag = "monitors",
+    request_body = WebsiteForm,
+    responses(
+        (status = 201, description = "The monitor was added", body = Website),
+        (status = 409, description = "The alias is already taken", body = ErrorBody),
+        (status = 422, description = "The settings are invalid", body = ErrorBody),
+    )
+)]
+async fn create_monitor(
+    State(state): State<AppState>,
+    body: Result<Json<WebsiteForm>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let website = insert_website(&state, json_body(body)?).await?;
+
+    Ok((StatusCode::CREATED, Json(website)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/monitors/{alias}",
+    tag = "monitors",
+    params(("alias" = String, Path, description = "Alias of the monitor")),
+    responses(
+        (status = 200, description = "The monitor with its stats and incidents", body = MonitorDetails),
+        (status = 404, description = "There's no monitor with this alias", body = ErrorBody),
+    )
+)]
+async fn get_monitor(State(state): State<AppState>, Path(alias): Path<String>)
+    -> Result<Json<MonitorDetails>, ApiError> {
+    let monitor = sqlx::query_as::<_, Website>("SELECT * FROM websites WHERE alias = $1")
+        .bind(&alias)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(Json(MonitorDetails {
+        certificate: Certificate::for_website(&state.db, monitor.id).await?,
+        daily_stats: get_daily_stats(&alias, &state.db).await?,
+        monthly_stats: get_monthly_stats(&alias, &state.db).await?,
+        incidents: Incident::for_website(&state.db, monitor.id).await?,
+        monitor,
+    }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/monitors/{alias}",
+    tag = "monitors",
+    params(("alias" = String, Path, description = "Alias of the monitor")),
+    request_body(
+        content = WebsiteForm,
+        description = "Every setting of the monitor, settings that are left out go back to their defaults"
+    ),
+    responses(
+        (status = 200, description = "The monitor was changed", body = Website),
+        (status = 404, description = "There's no monitor with this alias", body = ErrorBody),
+        (status = 409, description = "The new alias is already taken", body = ErrorBody),
+        (status = 422, description = "The settings are invalid", body = ErrorBody),
+    )
+)]
+async fn update_monitor(
+    State(state): State<AppState>,
+    Path(alias): Path<String>,
+    body: Result<Json<WebsiteForm>, JsonRejection>,
+) -> Result<Json<Website>, ApiError> {
+    let website = save_website(&state, &alias, json_body(body)?).await?;
+
+    Ok(Json(website))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/monitors/{alias}",
+    tag = "monitors",
+    params(("alias" = String, Path, description = "Alias of the monitor")),
+    responses(
+        (status = 204, description = "The monitor and its history were deleted"),
+        (status = 404, description = "There's no monitor with this alias", body = ErrorBody),
+    )
+)]
+async fn delete_monitor(State(state): State<AppState>, Path(alias): Path<String>)
+    -> Result<StatusCode, ApiError> {
+    remove_website(&state.db, &alias).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/monitors/{alias}/logs",
+    tag = "monitors",
+    params(("alias" = String, Path, description = "Alias of the monitor"), LogsQuery),
+    responses(
+        (status = 200, description = "The monitor's checks, newest first", body = [CheckLog]),
+        (status = 404, description = "There's no monitor with this alias", body = ErrorBody),
+        (status = 422, description = "The query is invalid", body = ErrorBody),
+    )
+)]
+async fn get_logs(
+    State(state): State<AppState>,
+    Path(alias): Path<String>,
+    query: Result<Query<LogsQuery>, QueryRejection>,
+) -> Result<Json<Vec<CheckLog>>, ApiError> {
+    let Query(query) = query.map_err(|e| ApiError::Validation(e.body_text()))?;
+
+    let website_id: i32 = sqlx::query_scalar("SELECT id FROM websites WHERE alias = $1")
+        .bind(&alias)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let logs = sqlx::query_as::<_, CheckLog>(
+        "SELECT id, created_at, is_up, status, error_kind, error_message, failed_assertion, json_values, \
+        dns_answers, response_time_ms, ttfb_ms, in_maintenance FROM logs \
+        WHERE website_id = $1 AND ($2::timestamptz IS NULL OR created_at < $2) \
+        ORDER BY created_at DESC LIMIT $3"
+    )
+        .bind(website_id)
+        .bind(query.before)
+        .bind(query.limit.clamp(1, 1000))
+        .fetch_all(&state.db)
+        .await?;
+
+    Ok(Json(logs))
+}
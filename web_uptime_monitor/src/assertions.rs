@@ -0,0 +1,348 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::Website;
+
+/*
+/ assertions on the response body that can turn a response
+/ with an accepted status into a failure, with the regex
+/ compiled once per check rather than once per attempt
+ */
+#[derive(Default)]
+pub struct BodyAssertions {
+    min_bytes: Option<usize>,
+    max_bytes: Option<usize>,
+    contains: Option<String>,
+    not_contains: Option<String>,
+    regex: Option<Regex>,
+}
+
+impl BodyAssertions {
+    pub fn for_website(website: &Website) -> Result<Self, String> {
+        let regex = website
+            .body_regex
+            .as_deref()
+            .map(|pattern| Regex::new(pattern).map_err(|e| format!("invalid body regex: {e}")))
+            .transpose()?;
+
+        Ok(Self {
+            min_bytes: website.min_body_bytes.map(|min| min.max(0) as usize),
+            max_bytes: website.max_body_bytes.map(|max| max.max(0) as usize),
+            contains: website.body_contains.clone(),
+            not_contains: website.body_not_contains.clone(),
+            regex,
+        })
+    }
+
+    /*
+    / a short explanation of the first assertion that didn't hold
+     */
+    pub fn failed_assertion(&self, body: &[u8]) -> Option<String> {
+        let size = body.len();
+
+        if let Some(min) = self.min_bytes {
+            if size < min {
+                return Some(format!("body is {size} bytes, expected at least {min}"));
+            }
+        }
+
+        if let Some(max) = self.max_bytes {
+            if size > max {
+                return Some(format!("body is {size} bytes, expected at most {max}"));
+            }
+        }
+
+        let text = String::from_utf8_lossy(body);
+
+        if let Some(expected) = &self.contains {
+            if !text.contains(expected.as_str()) {
+                return Some(format!("body does not contain {expected:?}"));
+            }
+        }
+
+        if let Some(unexpected) = &self.not_contains {
+            if text.contains(unexpected.as_str()) {
+                return Some(format!("body contains {unexpected:?}"));
+            }
+        }
+
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(&text) {
+                return Some(format!("body does not match /{regex}/"));
+            }
+        }
+
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Exists,
+    NotExists,
+}
+
+impl Operator {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Exists => "exists",
+            Self::NotExists => "not_exists",
+        }
+    }
+
+    fn takes_value(&self) -> bool {
+        !matches!(self, Self::Exists | Self::NotExists)
+    }
+}
+
+impl FromStr for Operator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            Self::Eq,
+            Self::Ne,
+            Self::Lt,
+            Self::Le,
+            Self::Gt,
+            Self::Ge,
+            Self::Exists,
+            Self::NotExists,
+        ]
+        .into_iter()
+        .find(|op| op.as_str() == s)
+        .ok_or_else(|| format!("unknown operator {s:?}"))
+    }
+}
+
+/*
+/ a single assertion against a json response, written as
+/ a JSON Pointer, an operator and (for comparisons) a value:
+/
+/   /status == "ok"
+/   /checks/db/latency_ms < 250
+/   /version exists
+/
+/ values are parsed as json, anything that isn't valid json
+/ is compared as a plain string so `/db == up` works too
+ */
+pub struct JsonAssertion {
+    pointer: String,
+    operator: Operator,
+    expected: Option<Value>,
+}
+
+impl FromStr for JsonAssertion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(3, char::is_whitespace);
+
+        let pointer = parts.next().unwrap_or_default();
+        if !pointer.is_empty() && !pointer.starts_with('/') {
+            return Err(format!("{pointer:?} is not a JSON Pointer, it should start with /"));
+        }
+
+        let operator: Operator = parts
+            .next()
+            .ok_or_else(|| format!("{s:?} is missing an operator"))?
+            .parse()?;
+
+        let expected = match (operator.takes_value(), parts.next().map(str::trim)) {
+            (true, Some(value)) if !value.is_empty() => {
+                Some(serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_owned())))
+            }
+            (true, _) => return Err(format!("{s:?} is missing a value to compare against")),
+            (false, None) => None,
+            (false, Some(_)) => return Err(format!("{} doesn't take a value", operator.as_str())),
+        };
+
+        Ok(Self {
+            pointer: pointer.to_owned(),
+            operator,
+            expected,
+        })
+    }
+}
+
+impl JsonAssertion {
+    /*
+    / parse the assertions of a website, one per line
+     */
+    pub fn parse_all(assertions: &str) -> Result<Vec<Self>, String> {
+        assertions
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::parse)
+            .collect()
+    }
+
+    fn holds(&self, actual: Option<&Value>) -> bool {
+        let expected = self.expected.as_ref();
+
+        match (self.operator, actual, expected) {
+            (Operator::Exists, actual, _) => actual.is_some(),
+            (Operator::NotExists, actual, _) => actual.is_none(),
+            (Operator::Eq, Some(actual), Some(expected)) => json_eq(actual, expected),
+            (Operator::Ne, actual, Some(expected)) => !actual.is_some_and(|actual| json_eq(actual, expected)),
+            (op, Some(actual), Some(expected)) => match compare_numbers(actual, expected) {
+                Some(ordering) => match op {
+                    Operator::Lt => ordering == Ordering::Less,
+                    Operator::Le => ordering != Ordering::Greater,
+                    Operator::Gt => ordering == Ordering::Greater,
+                    Operator::Ge => ordering != Ordering::Less,
+                    _ => false,
+                },
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match &self.expected {
+            Some(expected) => format!("{} {} {expected}", self.pointer, self.operator.as_str()),
+            None => format!("{} {}", self.pointer, self.operator.as_str()),
+        }
+    }
+}
+
+// 200 and 200.0 should be equal even though serde_json stores them differently
+fn json_eq(actual: &Value, expected: &Value) -> bool {
+    compare_numbers(actual, expected).map_or(actual == expected, |ordering| ordering == Ordering::Equal)
+}
+
+fn compare_numbers(actual: &Value, expected: &Value) -> Option<Ordering> {
+    actual.as_f64()?.partial_cmp(&expected.as_f64()?)
+}
+
+/*
+/ the result of evaluating a website's json assertions: the
+/ values we found at every pointer (stored in logs.json_values
+/ for debugging) and the first assertion that failed, if any
+ */
+pub struct JsonEvaluation {
+    pub values: Value,
+    pub failed_assertion: Option<String>,
+}
+
+pub fn evaluate_json_assertions(assertions: &[JsonAssertion], body: &[u8]) -> JsonEvaluation {
+    let document: Value = match serde_json::from_slice(body) {
+        Ok(document) => document,
+        Err(e) => {
+            return JsonEvaluation {
+                values: Value::Null,
+                failed_assertion: Some(format!("body is not valid JSON: {e}")),
+            }
+        }
+    };
+
+    let mut values = Map::new();
+    let mut failed_assertion = None;
+
+    for assertion in assertions {
+        let actual = document.pointer(&assertion.pointer);
+        values.insert(assertion.pointer.clone(), actual.cloned().unwrap_or(Value::Null));
+
+        if failed_assertion.is_none() && !assertion.holds(actual) {
+            let found = actual.map_or("nothing".to_owned(), Value::to_string);
+            failed_assertion = Some(format!("expected {}, found {found}", assertion.describe()));
+        }
+    }
+
+    JsonEvaluation {
+        values: Value::Object(values),
+        failed_assertion,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_body_size_and_contents() {
+        let assertions = BodyAssertions {
+            min_bytes: Some(4),
+            max_bytes: Some(20),
+            contains: Some("ok".to_owned()),
+            not_contains: Some("error".to_owned()),
+            regex: Some(Regex::new(r"^status: \w+$").unwrap()),
+        };
+
+        assert_eq!(assertions.failed_assertion(b"status: ok"), None);
+        assert_eq!(assertions.failed_assertion(b"ok"), Some("body is 2 bytes, expected at least 4".to_owned()));
+        assert_eq!(
+            assertions.failed_assertion(b"status: ok, and some more"),
+            Some("body is 25 bytes, expected at most 20".to_owned())
+        );
+        assert_eq!(assertions.failed_assertion(b"status: up"), Some("body does not contain \"ok\"".to_owned()));
+        assert_eq!(assertions.failed_assertion(b"ok: error"), Some("body contains \"error\"".to_owned()));
+        assert_eq!(
+            assertions.failed_assertion(b"ok status"),
+            Some(r"body does not match /^status: \w+$/".to_owned())
+        );
+    }
+
+    #[test]
+    fn no_body_assertions_always_hold() {
+        assert_eq!(BodyAssertions::default().failed_assertion(b""), None);
+    }
+
+    #[test]
+    fn parses_json_assertions() {
+        let assertions = JsonAssertion::parse_all("/status == \"ok\"\n\n/db == up\n/version exists").unwrap();
+
+        assert_eq!(assertions.len(), 3);
+        assert_eq!(assertions[0].expected, Some(Value::String("ok".to_owned())));
+        assert_eq!(assertions[1].expected, Some(Value::String("up".to_owned())));
+        assert_eq!(assertions[2].operator, Operator::Exists);
+
+        assert!("status == ok".parse::<JsonAssertion>().is_err());
+        assert!("/status".parse::<JsonAssertion>().is_err());
+        assert!("/status ~= ok".parse::<JsonAssertion>().is_err());
+        assert!("/status ==".parse::<JsonAssertion>().is_err());
+        assert!("/status exists yes".parse::<JsonAssertion>().is_err());
+    }
+
+    #[test]
+    fn evaluates_json_assertions() {
+        let assertions = JsonAssertion::parse_all(
+            "/status == ok\n/latency_ms < 250\n/code == 200.0\n/error not_exists\n/region != eu"
+        )
+        .unwrap();
+        let body = br#"{"status": "ok", "latency_ms": 120, "code": 200, "region": "us"}"#;
+
+        let evaluation = evaluate_json_assertions(&assertions, body);
+
+        assert_eq!(evaluation.failed_assertion, None);
+        assert_eq!(evaluation.values["/latency_ms"], 120);
+        assert_eq!(evaluation.values["/error"], Value::Null);
+    }
+
+    #[test]
+    fn reports_the_first_failed_json_assertion() {
+        let assertions = JsonAssertion::parse_all("/latency_ms <= 100\n/status == ok").unwrap();
+
+        let evaluation = evaluate_json_assertions(&assertions, br#"{"status": "down", "latency_ms": 120}"#);
+        assert_eq!(evaluation.failed_assertion, Some("expected /latency_ms <= 100, found 120".to_owned()));
+
+        let evaluation = evaluate_json_assertions(&assertions, b"<html>");
+        assert!(evaluation.failed_assertion.unwrap().starts_with("body is not valid JSON"));
+    }
+}
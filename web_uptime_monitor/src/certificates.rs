@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::tls::TlsInfo;
+use reqwest::{Client, Response};
+use serde::Serialize;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use x509_parser::extensions::GeneralName;
+use x509_parser::parse_x509_certificate;
+
+use crate::Website;
+
+/*
+/ the parts of a website's tls certificate we keep track of.
+/ reqwest only hands us the leaf of the chain, so the issuer
+/ here is whoever signed the leaf
+ */
+pub struct PeerCertificate {
+    pub subject: String,
+    pub issuer: String,
+    pub sans: Vec<String>,
+    pub not_after: DateTime<Utc>,
+    pub chain_valid: bool,
+}
+
+impl PeerCertificate {
+    /*
+    / read the certificate off a response, this only works
+    / for clients built with tls_info(true)
+     */
+    pub fn from_response(response: &Response, chain_valid: bool) -> Option<Self> {
+        let der = response.extensions().get::<TlsInfo>()?.peer_certificate()?;
+        let (_, cert) = parse_x509_certificate(der).ok()?;
+
+        let sans = cert
+            .subject_alternative_name()
+            .ok()
+            .flatten()
+            .map(|san| {
+                san.value
+                    .general_names
+                    .iter()
+                    .filter_map(|name| match name {
+                        GeneralName::DNSName(dns_name) => Some(dns_name.to_string()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(Self {
+            subject: cert.subject().to_string(),
+            issuer: cert.issuer().to_string(),
+            sans,
+            not_after: DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0)?,
+            chain_valid,
+        })
+    }
+
+    /*
+    / when the certificate of a website doesn't validate the
+    / check never gets a response, so we connect once more
+    / without validation just to see what's being served. the
+    / client has to accept invalid certificates and give out
+    / tls info, and gets as long as the check itself
+     */
+    pub async fn fetch_unverified(client: &Client, website: &Website) -> Option<Self> {
+        let response = client
+            .head(&website.url)
+            .timeout(Duration::from_secs(website.timeout_secs as u64))
+            .send()
+            .await
+            .ok()?;
+
+        Self::from_response(&response, false)
+    }
+
+    pub fn days_until_expiry(&self) -> i64 {
+        (self.not_after - Utc::now()).num_days()
+    }
+
+    /*
+    / we only keep the latest certificate seen for a website
+     */
+    pub async fn record(&self, db: &PgPool, website: &Website) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO certificates (website_id, subject, issuer, sans, not_after, chain_valid) \
+            VALUES ($1, $2, $3, $4, $5, $6) \
+            ON CONFLICT (website_id) DO UPDATE SET \
+            subject = EXCLUDED.subject, issuer = EXCLUDED.issuer, sans = EXCLUDED.sans, \
+            not_after = EXCLUDED.not_after, chain_valid = EXCLUDED.chain_valid, checked_at = current_timestamp"
+        )
+            .bind(website.id)
+            .bind(&self.subject)
+            .bind(&self.issuer)
+            .bind(&self.sans)
+            .bind(self.not_after)
+            .bind(self.chain_valid)
+            .execute(db)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/*
+/ a stored certificate, as shown on single_website.html
+ */
+#[derive(sqlx::FromRow, Serialize, ToSchema)]
+pub struct Certificate {
+    pub subject: String,
+    pub issuer: String,
+    pub sans: Vec<String>,
+    pub not_after: DateTime<Utc>,
+    pub chain_valid: bool,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl Certificate {
+    pub async fn for_website(db: &PgPool, website_id: i32) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM certificates WHERE website_id = $1")
+            .bind(website_id)
+            .fetch_optional(db)
+            .await
+    }
+
+    pub fn days_until_expiry(&self) -> i64 {
+        (self.not_after - Utc::now()).num_days()
+    }
+}
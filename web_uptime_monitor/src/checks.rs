@@ -0,0 +1,826 @@
+use std::error::Error as StdError;
+use std::future::Future;
+use std::io;
+use std::iter;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Method, RequestBuilder};
+use serde_json::Value;
+use sqlx::PgPool;
+use tokio::time::{self, Duration, Instant};
+
+use crate::assertions::{self, BodyAssertions, JsonAssertion};
+use crate::certificates::PeerCertificate;
+use crate::crypto::SecretCipher;
+use crate::incidents::{Incident, Transition};
+use crate::{MonitorKind, Website};
+
+mod dns;
+mod heartbeat;
+mod tcp;
+
+pub use dns::{resolver_address, target as dns_target, DNS_RECORD_TYPES};
+pub use tcp::target as tcp_target;
+
+/*
+/ how long to wait between confirmation attempts of a failed check
+ */
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/*
+/ the different ways a check can fail before we get a
+/ status code back, stored in logs.error_kind
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    Dns,
+    ConnectionRefused,
+    Tls,
+    Timeout,
+    BodyRead,
+    Request,
+    Config,
+    Database,
+}
+
+impl ErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Dns => "dns",
+            Self::ConnectionRefused => "connection_refused",
+            Self::Tls => "tls",
+            Self::Timeout => "timeout",
+            Self::BodyRead => "body_read",
+            Self::Request => "request",
+            Self::Config => "config",
+            Self::Database => "database",
+        }
+    }
+
+    /*
+    / reqwest only tells us whether an error happened while
+    / connecting, so we walk the source chain to find out what
+    / actually went wrong underneath. reqwest's own message has
+    / the url in it, which can say tls or ssl of its own accord,
+    / so only the errors below it are looked at
+     */
+    fn classify(e: &reqwest::Error) -> Self {
+        if e.is_timeout() {
+            return Self::Timeout;
+        }
+
+        if e.is_body() || e.is_decode() {
+            return Self::BodyRead;
+        }
+
+        Self::classify_sources(e.source())
+    }
+
+    fn classify_chain(e: &(dyn StdError + 'static)) -> Self {
+        Self::classify_sources(Some(e))
+    }
+
+    /*
+    / io errors anywhere in the chain say the most, the messages
+    / of the errors are only a fallback
+     */
+    fn classify_sources(first: Option<&(dyn StdError + 'static)>) -> Self {
+        let chain = || iter::successors(first, |err| (*err).source());
+
+        let io_kind = chain()
+            .filter_map(|err| err.downcast_ref::<io::Error>())
+            .find_map(|io_err| match io_err.kind() {
+                io::ErrorKind::ConnectionRefused => Some(Self::ConnectionRefused),
+                io::ErrorKind::TimedOut => Some(Self::Timeout),
+                _ => None,
+            });
+
+        if let Some(kind) = io_kind {
+            return kind;
+        }
+
+        for err in chain() {
+            let message = err.to_string().to_lowercase();
+            if message.contains("dns error") || message.contains("failed to lookup address") {
+                return Self::Dns;
+            }
+            if message.contains("certificate") || message.contains("tls") || message.contains("ssl") {
+                return Self::Tls;
+            }
+        }
+
+        Self::Request
+    }
+}
+
+/*
+/ the status codes a website may answer with and still
+/ count as up, written like "200-299,301"
+ */
+pub struct AcceptedStatuses(Vec<RangeInclusive<u16>>);
+
+impl AcceptedStatuses {
+    pub fn contains(&self, status: u16) -> bool {
+        self.0.iter().any(|range| range.contains(&status))
+    }
+}
+
+impl Default for AcceptedStatuses {
+    fn default() -> Self {
+        Self(vec![200..=299])
+    }
+}
+
+impl FromStr for AcceptedStatuses {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_code = |code: &str| match code.trim().parse::<u16>() {
+            Ok(code) if (100..=599).contains(&code) => Ok(code),
+            _ => Err(format!("{code:?} is not a valid status code")),
+        };
+
+        let ranges = s
+            .split(',')
+            .filter(|part| !part.trim().is_empty())
+            .map(|part| match part.split_once('-') {
+                Some((start, end)) => {
+                    let (start, end) = (parse_code(start)?, parse_code(end)?);
+                    if start > end {
+                        return Err(format!("{part:?} is an empty range"));
+                    }
+                    Ok(start..=end)
+                }
+                None => parse_code(part).map(|code| code..=code),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if ranges.is_empty() {
+            return Err("at least one status code is required".to_owned());
+        }
+
+        Ok(Self(ranges))
+    }
+}
+
+/*
+/ the http methods a monitor can send
+ */
+pub const HTTP_METHODS: [&str; 7] = ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"];
+
+/*
+/ custom headers are written one per line as "Name: value"
+ */
+pub fn parse_headers(headers: &str) -> Result<HeaderMap, String> {
+    let mut header_map = HeaderMap::new();
+
+    for line in headers.lines().filter(|line| !line.trim().is_empty()) {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| format!("{line:?} should look like \"Name: value\""))?;
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| format!("{name:?} is not a valid header name"))?;
+        let value = HeaderValue::from_str(value.trim())
+            .map_err(|_| format!("the value of {name} is not a valid header value"))?;
+
+        header_map.append(name, value);
+    }
+
+    Ok(header_map)
+}
+
+/*
+/ how a monitor authenticates itself, the secret part is
+/ stored encrypted in websites.auth_secret
+ */
+enum Auth {
+    None,
+    Basic { username: String, password: String },
+    Bearer(String),
+}
+
+/*
+/ everything needed to build the request of a monitor, with
+/ its encrypted headers and credentials already decrypted
+ */
+struct RequestSpec {
+    method: Method,
+    headers: HeaderMap,
+    body: Option<String>,
+    auth: Auth,
+}
+
+impl RequestSpec {
+    fn for_website(website: &Website, cipher: Option<&SecretCipher>) -> Result<Self, String> {
+        let decrypt = |encrypted: &[u8]| match cipher {
+            Some(cipher) => cipher.decrypt(encrypted),
+            None => Err("MONITOR_SECRETS_KEY is not configured, can't decrypt monitor secrets".to_owned()),
+        };
+
+        let method = Method::from_bytes(website.http_method.as_bytes())
+            .map_err(|_| format!("{:?} is not a valid http method", website.http_method))?;
+
+        let headers = match &website.request_headers {
+            Some(encrypted) => parse_headers(&decrypt(encrypted)?)?,
+            None => HeaderMap::new(),
+        };
+
+        let auth = match (website.auth_kind.as_str(), &website.auth_secret) {
+            ("basic", Some(secret)) => Auth::Basic {
+                username: website.auth_username.clone().unwrap_or_default(),
+                password: decrypt(secret)?,
+            },
+            ("bearer", Some(secret)) => Auth::Bearer(decrypt(secret)?),
+            ("none", _) => Auth::None,
+            (kind, _) => return Err(format!("{kind} auth is missing its secret")),
+        };
+
+        Ok(Self {
+            method,
+            headers,
+            body: website.request_body.clone(),
+            auth,
+        })
+    }
+
+    fn build(&self, client: &Client, url: &str) -> RequestBuilder {
+        let mut request = client
+            .request(self.method.clone(), url)
+            .headers(self.headers.clone());
+
+        if let Some(body) = &self.body {
+            request = request.body(body.clone());
+        }
+
+        match &self.auth {
+            Auth::None => request,
+            Auth::Basic { username, password } => request.basic_auth(username, Some(password)),
+            Auth::Bearer(token) => request.bearer_auth(token),
+        }
+    }
+}
+
+pub struct CheckError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl From<reqwest::Error> for CheckError {
+    fn from(e: reqwest::Error) -> Self {
+        Self {
+            kind: ErrorKind::classify(&e),
+            message: error_chain(&e),
+        }
+    }
+}
+
+impl From<io::Error> for CheckError {
+    fn from(e: io::Error) -> Self {
+        Self {
+            kind: ErrorKind::classify_chain(&e),
+            message: error_chain(&e),
+        }
+    }
+}
+
+/*
+/ reqwest's top level message is usually just "error sending
+/ request", the useful part is further down the chain
+ */
+fn error_chain(e: &dyn StdError) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+
+    message
+}
+
+/*
+/ the result of checking a website once, either a status
+/ code or the reason we couldn't get one. timings are only
+/ known once a response started coming back
+ */
+pub struct CheckOutcome {
+    pub is_up: bool,
+    pub status: Option<i16>,
+    pub error: Option<CheckError>,
+    pub failed_assertion: Option<String>,
+    pub json_values: Option<Value>,
+    pub dns_answers: Option<Vec<String>>,
+    pub timings: Option<Timings>,
+    pub certificate: Option<PeerCertificate>,
+    pub certificate_warning: Option<String>,
+    pub attempts: Vec<CheckAttempt>,
+}
+
+impl CheckOutcome {
+    fn failed(error: CheckError, status: Option<i16>, timings: Option<Timings>) -> Self {
+        Self {
+            is_up: false,
+            status,
+            error: Some(error),
+            failed_assertion: None,
+            json_values: None,
+            dns_answers: None,
+            timings,
+            certificate: None,
+            certificate_warning: None,
+            attempts: Vec::new(),
+        }
+    }
+}
+
+/*
+/ the raw result of one attempt of a check that had to be
+/ confirmed, kept in check_attempts so a check that was
+/ recorded as up or down can be traced back to its attempts
+ */
+pub struct CheckAttempt {
+    pub is_up: bool,
+    pub status: Option<i16>,
+    pub error_kind: Option<ErrorKind>,
+    pub error_message: Option<String>,
+    pub failed_assertion: Option<String>,
+    pub response_time_ms: Option<i32>,
+}
+
+impl From<&CheckOutcome> for CheckAttempt {
+    fn from(outcome: &CheckOutcome) -> Self {
+        Self {
+            is_up: outcome.is_up,
+            status: outcome.status,
+            error_kind: outcome.error.as_ref().map(|e| e.kind),
+            error_message: outcome.error.as_ref().map(|e| e.message.clone()),
+            failed_assertion: outcome.failed_assertion.clone(),
+            response_time_ms: outcome.timings.as_ref().map(|t| t.response_time_ms),
+        }
+    }
+}
+
+/*
+/ reqwest doesn't expose the dns, connect and tls phases of
+/ a request (and pooled connections skip them entirely), so
+/ we measure time to first byte and the total response time
+ */
+pub struct Timings {
+    pub ttfb_ms: i32,
+    pub response_time_ms: i32,
+}
+
+/*
+/ the request to send and the rules its response is judged
+/ by, prepared once per check rather than once per attempt
+ */
+struct CheckRules {
+    request: RequestSpec,
+    accepted_statuses: AcceptedStatuses,
+    body_assertions: BodyAssertions,
+    json_assertions: Vec<JsonAssertion>,
+}
+
+impl CheckRules {
+    fn for_website(website: &Website, cipher: Option<&SecretCipher>) -> Result<Self, String> {
+        let request = RequestSpec::for_website(website, cipher)?;
+        let body_assertions = BodyAssertions::for_website(website)?;
+
+        let accepted_statuses = website.accepted_statuses.parse().unwrap_or_else(|e| {
+            println!("Invalid accepted statuses for {}, using the default: {e}", website.alias);
+            AcceptedStatuses::default()
+        });
+
+        let json_assertions = website
+            .json_assertions
+            .as_deref()
+            .map(JsonAssertion::parse_all)
+            .transpose()
+            .unwrap_or_else(|e| {
+                println!("Invalid json assertions for {}, ignoring them: {e}", website.alias);
+                None
+            })
+            .unwrap_or_default();
+
+        Ok(Self {
+            request,
+            accepted_statuses,
+            body_assertions,
+            json_assertions,
+        })
+    }
+}
+
+/*
+/ runs checks and records their outcomes, shared by every
+/ check the scheduler starts
+ */
+pub struct Checker {
+    client: Client,
+    // only used to see which certificate a website serves when it doesn't validate
+    unverified_client: Client,
+    db: PgPool,
+    cipher: Option<SecretCipher>,
+}
+
+impl Checker {
+    pub fn new(db: PgPool, cipher: Option<SecretCipher>) -> Self {
+        Self {
+            client: Client::builder()
+                .tls_info(true)
+                .build()
+                .expect("tls backend should be available"),
+            unverified_client: Client::builder()
+                .danger_accept_invalid_certs(true)
+                .tls_info(true)
+                .build()
+                .expect("tls backend should be available"),
+            db,
+            cipher,
+        }
+    }
+
+    /*
+    / check a monitor. when the first attempt fails it's
+    / re-checked up to the number of retries configured for it,
+    / and only counts as down once confirm_failures attempts
+    / have failed (all of them if that isn't set)
+     */
+    pub async fn check(&self, website: &Website) -> CheckOutcome {
+        let rules = match CheckRules::for_website(website, self.cipher.as_ref()) {
+            Ok(rules) => rules,
+            Err(message) => {
+                let error = CheckError { kind: ErrorKind::Config, message };
+                return CheckOutcome::failed(error, None, None);
+            }
+        };
+
+        let mut outcome = self.check_once(website, &rules).await;
+
+        if !outcome.is_up {
+            outcome = self.confirm_failure(website, &rules, outcome).await;
+        }
+
+        if website.url.starts_with("https://") {
+            let tls_failed = matches!(&outcome.error, Some(e) if e.kind == ErrorKind::Tls);
+            if tls_failed && outcome.certificate.is_none() {
+                outcome.certificate = PeerCertificate::fetch_unverified(&self.unverified_client, website).await;
+            }
+
+            outcome.certificate_warning = certificate_warning(website, &outcome);
+        }
+
+        outcome
+    }
+
+    async fn confirm_failure(&self, website: &Website, rules: &CheckRules, first: CheckOutcome) -> CheckOutcome {
+        confirm(website.retries, website.confirm_failures, first, RETRY_DELAY, || self.check_once(website, rules)).await
+    }
+
+    async fn check_once(&self, website: &Website, rules: &CheckRules) -> CheckOutcome {
+        match website.kind {
+            MonitorKind::Http => check_http(&self.client, website, rules).await,
+            MonitorKind::Tcp => tcp::check(website).await,
+            MonitorKind::Dns => dns::check(&self.db, website).await,
+            MonitorKind::Heartbeat => heartbeat::check(&self.db, website).await,
+        }
+    }
+
+    /*
+    / save the outcome of a check to the logs table, along with
+    / the certificate the website presented if it has one, and
+    / open or resolve the website's incidents if its state or
+    / that of its certificate changed. checks run during
+    / maintenance are tagged and leave the incidents alone
+     */
+    pub async fn record(
+        &self,
+        website: &Website,
+        outcome: &CheckOutcome,
+        in_maintenance: bool,
+    ) -> Result<Vec<Transition>, sqlx::Error> {
+        let log_id: i32 = sqlx::query_scalar(
+            "INSERT INTO logs \
+            (website_id, is_up, status, error_kind, error_message, failed_assertion, json_values, \
+            dns_answers, response_time_ms, ttfb_ms, in_maintenance) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) \
+            RETURNING id"
+        )
+            .bind(website.id)
+            .bind(outcome.is_up)
+            .bind(outcome.status)
+            .bind(outcome.error.as_ref().map(|e| e.kind.as_str()))
+            .bind(outcome.error.as_ref().map(|e| e.message.as_str()))
+            .bind(&outcome.failed_assertion)
+            .bind(&outcome.json_values)
+            .bind(&outcome.dns_answers)
+            .bind(outcome.timings.as_ref().map(|t| t.response_time_ms))
+            .bind(outcome.timings.as_ref().map(|t| t.ttfb_ms))
+            .bind(in_maintenance)
+            .fetch_one(&self.db)
+            .await?;
+
+        for (attempt, result) in outcome.attempts.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO check_attempts \
+                (log_id, attempt, is_up, status, error_kind, error_message, failed_assertion, response_time_ms) \
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+            )
+                .bind(log_id)
+                .bind(attempt as i32 + 1)
+                .bind(result.is_up)
+                .bind(result.status)
+                .bind(result.error_kind.map(|kind| kind.as_str()))
+                .bind(&result.error_message)
+                .bind(&result.failed_assertion)
+                .bind(result.response_time_ms)
+                .execute(&self.db)
+                .await?;
+        }
+
+        if let Some(certificate) = &outcome.certificate {
+            certificate.record(&self.db, website).await?;
+        }
+
+        if in_maintenance {
+            return Ok(Vec::new());
+        }
+
+        let mut transitions: Vec<Transition> = Incident::track(&self.db, website, outcome).await?.into_iter().collect();
+
+        // checks that didn't get a certificate can't tell whether it was renewed
+        if outcome.certificate.is_some() {
+            let warning = outcome.certificate_warning.as_deref();
+            transitions.extend(Incident::track_certificate(&self.db, website, warning).await?);
+        }
+
+        Ok(transitions)
+    }
+}
+
+/*
+/ re-run a failed check with attempt, waiting delay before
+/ every attempt, until confirm_failures attempts failed or
+/ too few are left for that. the outcome is the last failure
+/ if it was confirmed and the last success otherwise, and it
+/ carries every attempt if there was more than one
+ */
+async fn confirm<F, Fut>(
+    retries: i32,
+    confirm_failures: Option<i32>,
+    first: CheckOutcome,
+    delay: Duration,
+    mut attempt: F,
+) -> CheckOutcome
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = CheckOutcome>,
+{
+    let max_attempts = retries.max(0) + 1;
+    let needed_failures = confirm_failures.unwrap_or(max_attempts).clamp(1, max_attempts);
+
+    let mut attempts = vec![CheckAttempt::from(&first)];
+    let mut failures = 1;
+    let mut last_failure = first;
+    let mut last_success = None;
+
+    // stop as soon as the failure is confirmed or can't be confirmed anymore
+    while failures < needed_failures && failures + (max_attempts - attempts.len() as i32) >= needed_failures {
+        time::sleep(delay).await;
+
+        let outcome = attempt().await;
+        attempts.push(CheckAttempt::from(&outcome));
+
+        if outcome.is_up {
+            last_success = Some(outcome);
+        } else {
+            failures += 1;
+            last_failure = outcome;
+        }
+    }
+
+    let mut outcome = match last_success {
+        Some(success) if failures < needed_failures => success,
+        _ => last_failure,
+    };
+
+    if attempts.len() > 1 {
+        outcome.attempts = attempts;
+    }
+
+    outcome
+}
+
+/*
+/ a certificate that's about to expire doesn't make the
+/ website any less up, it opens a certificate incident of its
+/ own so it's noticed while there's still time to renew it
+ */
+fn certificate_warning(website: &Website, outcome: &CheckOutcome) -> Option<String> {
+    let certificate = outcome.certificate.as_ref()?;
+    let days_left = certificate.days_until_expiry();
+
+    (website.cert_expiry_days > 0 && days_left < website.cert_expiry_days as i64).then(|| {
+        format!(
+            "certificate for {} expires in {days_left} days on {}",
+            certificate.subject, certificate.not_after
+        )
+    })
+}
+
+async fn check_http(client: &Client, website: &Website, rules: &CheckRules) -> CheckOutcome {
+    let timeout = Duration::from_secs(website.timeout_secs as u64);
+    let started = Instant::now();
+
+    let request = rules.request.build(client, &website.url).timeout(timeout);
+
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => return CheckOutcome::failed(e.into(), None, None),
+    };
+
+    let certificate = PeerCertificate::from_response(&response, true);
+    let status = response.status().as_u16();
+    let status_accepted = rules.accepted_statuses.contains(status);
+    let ttfb_ms = elapsed_ms(started);
+
+    // read the whole body so a connection dropped halfway through counts as a failure
+    let body = match response.bytes().await {
+        Ok(body) => body,
+        Err(e) => {
+            let timings = Timings {
+                ttfb_ms,
+                response_time_ms: elapsed_ms(started),
+            };
+            return CheckOutcome::failed(e.into(), Some(status as i16), Some(timings));
+        }
+    };
+
+    let mut failed_assertion = None;
+    let mut json_values = None;
+
+    if status_accepted {
+        failed_assertion = rules.body_assertions.failed_assertion(&body);
+
+        if !rules.json_assertions.is_empty() {
+            let evaluation = assertions::evaluate_json_assertions(&rules.json_assertions, &body);
+            failed_assertion = failed_assertion.or(evaluation.failed_assertion);
+            json_values = Some(evaluation.values);
+        }
+    }
+
+    CheckOutcome {
+        is_up: status_accepted && failed_assertion.is_none(),
+        status: Some(status as i16),
+        error: None,
+        failed_assertion,
+        json_values,
+        dns_answers: None,
+        timings: Some(Timings {
+            ttfb_ms,
+            response_time_ms: elapsed_ms(started),
+        }),
+        certificate,
+        certificate_warning: None,
+        attempts: Vec::new(),
+    }
+}
+
+fn elapsed_ms(started: Instant) -> i32 {
+    started.elapsed().as_millis().try_into().unwrap_or(i32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn down(status: i16) -> CheckOutcome {
+        let error = CheckError {
+            kind: ErrorKind::Request,
+            message: "failed".to_owned(),
+        };
+        CheckOutcome::failed(error, Some(status), None)
+    }
+
+    fn up() -> CheckOutcome {
+        CheckOutcome {
+            is_up: true,
+            error: None,
+            ..CheckOutcome::failed(CheckError { kind: ErrorKind::Request, message: String::new() }, Some(200), None)
+        }
+    }
+
+    /*
+    / confirm a failure with the given outcomes for the retries,
+    / returning the outcome and how many retries were run
+     */
+    async fn confirm_with(retries: i32, confirm_failures: Option<i32>, outcomes: Vec<CheckOutcome>) -> (CheckOutcome, usize) {
+        let mut outcomes = outcomes.into_iter();
+        let mut run = 0;
+
+        let outcome = confirm(retries, confirm_failures, down(500), Duration::ZERO, || {
+            run += 1;
+            let outcome = outcomes.next().expect("ran more retries than expected");
+            async move { outcome }
+        })
+        .await;
+
+        (outcome, run)
+    }
+
+    #[tokio::test]
+    async fn urls_that_mention_tls_dont_make_every_error_a_tls_error() {
+        // a port nothing listens on anymore refuses connections
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let url = format!("http://127.0.0.1:{port}/ssl/certificates/tls");
+        let error = Client::new().get(&url).send().await.unwrap_err();
+
+        assert!(error.to_string().contains("/ssl/certificates/tls"));
+        assert_eq!(CheckError::from(error).kind, ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    fn classifies_io_errors_before_their_messages() {
+        let refused = io::Error::new(io::ErrorKind::ConnectionRefused, "tls handshake never started");
+        assert_eq!(CheckError::from(refused).kind, ErrorKind::ConnectionRefused);
+
+        let tls = io::Error::new(io::ErrorKind::InvalidData, "invalid peer certificate: Expired");
+        assert_eq!(CheckError::from(tls).kind, ErrorKind::Tls);
+
+        let other = io::Error::other("connection reset");
+        assert_eq!(CheckError::from(other).kind, ErrorKind::Request);
+    }
+
+    #[test]
+    fn parses_accepted_statuses() {
+        let statuses: AcceptedStatuses = "200-299, 301,404".parse().unwrap();
+
+        assert!(statuses.contains(200));
+        assert!(statuses.contains(299));
+        assert!(statuses.contains(301));
+        assert!(statuses.contains(404));
+        assert!(!statuses.contains(302));
+        assert!(!statuses.contains(500));
+
+        assert!("".parse::<AcceptedStatuses>().is_err());
+        assert!("299-200".parse::<AcceptedStatuses>().is_err());
+        assert!("600".parse::<AcceptedStatuses>().is_err());
+        assert!("2xx".parse::<AcceptedStatuses>().is_err());
+    }
+
+    #[test]
+    fn parses_headers() {
+        let headers = parse_headers("X-Api-Key: abc:def\n\nAccept: text/plain\nAccept: application/json").unwrap();
+
+        assert_eq!(headers["x-api-key"], "abc:def");
+        assert_eq!(headers.get_all("accept").iter().count(), 2);
+
+        assert!(parse_headers("X-Api-Key abc").is_err());
+        assert!(parse_headers("X Api Key: abc").is_err());
+        assert!(parse_headers("X-Api-Key: a\u{7f}b").is_err());
+    }
+
+    #[tokio::test]
+    async fn failures_need_every_attempt_by_default() {
+        let (outcome, run) = confirm_with(2, None, vec![down(502), down(503)]).await;
+
+        assert!(!outcome.is_up);
+        assert_eq!(outcome.status, Some(503));
+        assert_eq!(run, 2);
+        assert_eq!(outcome.attempts.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn a_success_stops_a_failure_that_cant_be_confirmed_anymore() {
+        let (outcome, run) = confirm_with(2, None, vec![up()]).await;
+
+        assert!(outcome.is_up);
+        assert_eq!(run, 1);
+        assert_eq!(outcome.attempts.iter().map(|attempt| attempt.is_up).collect::<Vec<_>>(), [false, true]);
+    }
+
+    #[tokio::test]
+    async fn failures_are_confirmed_by_enough_failed_attempts() {
+        let (outcome, run) = confirm_with(3, Some(2), vec![up(), down(503)]).await;
+
+        assert!(!outcome.is_up);
+        assert_eq!(run, 2);
+        assert_eq!(outcome.attempts.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn failures_without_retries_are_down_straight_away() {
+        let (outcome, run) = confirm_with(0, None, vec![]).await;
+
+        assert!(!outcome.is_up);
+        assert_eq!(run, 0);
+        assert!(outcome.attempts.is_empty());
+    }
+}
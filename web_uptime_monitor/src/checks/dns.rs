@@ -0,0 +1,348 @@
+use std::net::{IpAddr, SocketAddr};
+
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::error::{ResolveError, ResolveErrorKind};
+use hickory_resolver::proto::rr::RecordType;
+use hickory_resolver::{system_conf, TokioAsyncResolver};
+use reqwest::Url;
+use sqlx::PgPool;
+use tokio::time::{Duration, Instant};
+
+use super::{elapsed_ms, CheckError, CheckOutcome, ErrorKind, Timings};
+use crate::Website;
+
+/*
+/ the record types a dns monitor can ask for
+ */
+pub const DNS_RECORD_TYPES: [&str; 5] = ["A", "AAAA", "CNAME", "MX", "TXT"];
+
+/*
+/ a dns monitor resolves dns://hostname for one record type.
+/ if expected answers are configured the answer set has to
+/ match them exactly, otherwise any change compared to the
+/ previous check is reported, so a hijacked or botched record
+/ shows up as an incident either way
+ */
+pub async fn check(db: &PgPool, website: &Website) -> CheckOutcome {
+    let started = Instant::now();
+
+    let (resolver, name, record_type) = match prepare(website) {
+        Ok(prepared) => prepared,
+        Err(message) => return CheckOutcome::failed(CheckError { kind: ErrorKind::Config, message }, None, None),
+    };
+
+    let answers = match resolve(&resolver, &name, record_type).await {
+        Ok(answers) => answers,
+        Err(e) => return CheckOutcome::failed(e.into(), None, None),
+    };
+
+    let failed_assertion = match &website.dns_expected {
+        Some(expected) => expected_mismatch(expected, &answers),
+        None => answers_changed(previous_answers(db, website).await, &answers),
+    };
+
+    let resolve_ms = elapsed_ms(started);
+
+    CheckOutcome {
+        is_up: failed_assertion.is_none(),
+        status: None,
+        error: None,
+        failed_assertion,
+        json_values: None,
+        dns_answers: Some(answers),
+        timings: Some(Timings {
+            ttfb_ms: resolve_ms,
+            response_time_ms: resolve_ms,
+        }),
+        certificate: None,
+        certificate_warning: None,
+        attempts: Vec::new(),
+    }
+}
+
+fn prepare(website: &Website) -> Result<(TokioAsyncResolver, String, RecordType), String> {
+    let name = target(&website.url)?;
+    let record_type = website
+        .dns_record_type
+        .parse::<RecordType>()
+        .ok()
+        .filter(|_| DNS_RECORD_TYPES.contains(&website.dns_record_type.as_str()))
+        .ok_or_else(|| format!("{} is not a supported record type", website.dns_record_type))?;
+
+    let timeout = Duration::from_secs(website.timeout_secs as u64);
+    let resolver = resolver(website.dns_resolver.as_deref(), timeout)?;
+
+    Ok((resolver, name, record_type))
+}
+
+/*
+/ a resolver that asks the given ip:port, or the system's
+/ resolvers if there's none
+ */
+fn resolver(address: Option<&str>, timeout: Duration) -> Result<TokioAsyncResolver, String> {
+    let (config, mut options) = match address {
+        Some(address) => {
+            let address = resolver_address(address)?;
+            let name_servers = NameServerConfigGroup::from_ips_clear(&[address.ip()], address.port(), true);
+            (ResolverConfig::from_parts(None, vec![], name_servers), ResolverOpts::default())
+        }
+        None => system_conf::read_system_conf().map_err(|e| format!("couldn't read system resolver config: {e}"))?,
+    };
+
+    // every check should actually ask the resolver, with the monitor's own timeout
+    options.timeout = timeout;
+    options.attempts = 1;
+    options.cache_size = 0;
+
+    Ok(TokioAsyncResolver::tokio(config, options))
+}
+
+/*
+/ the normalized answers of the given type, sorted and
+/ without duplicates so they can be compared as a set
+ */
+async fn resolve(resolver: &TokioAsyncResolver, name: &str, record_type: RecordType)
+    -> Result<Vec<String>, ResolveError> {
+    let lookup = resolver.lookup(name, record_type).await?;
+
+    let mut answers: Vec<String> = lookup
+        .record_iter()
+        .filter(|record| record.record_type() == record_type)
+        .filter_map(|record| record.data())
+        .map(|data| normalize(&data.to_string()))
+        .collect();
+    answers.sort();
+    answers.dedup();
+
+    Ok(answers)
+}
+
+pub fn target(url: &str) -> Result<String, String> {
+    let url = Url::parse(url).map_err(|e| format!("{url:?} is not a valid dns target: {e}"))?;
+
+    match (url.scheme(), url.host_str()) {
+        ("dns", Some(host)) => Ok(host.to_owned()),
+        _ => Err(format!("{url} should look like dns://hostname")),
+    }
+}
+
+/*
+/ resolvers are written as ip:port, or just an ip for port 53
+ */
+pub fn resolver_address(resolver: &str) -> Result<SocketAddr, String> {
+    resolver
+        .parse::<SocketAddr>()
+        .or_else(|_| resolver.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| format!("{resolver:?} is not a valid resolver address"))
+}
+
+/*
+/ names come back fully qualified and txt records quoted,
+/ normalize both so expected values can be written naturally
+ */
+fn normalize(answer: &str) -> String {
+    answer.trim().trim_matches('"').trim_end_matches('.').to_lowercase()
+}
+
+fn expected_answers(expected: &str) -> Vec<String> {
+    let mut expected: Vec<String> = expected
+        .split(',')
+        .map(normalize)
+        .filter(|answer| !answer.is_empty())
+        .collect();
+    expected.sort();
+    expected.dedup();
+    expected
+}
+
+fn expected_mismatch(expected: &str, answers: &[String]) -> Option<String> {
+    let expected = expected_answers(expected);
+
+    (answers != expected).then(|| format!("expected {expected:?}, resolved {answers:?}"))
+}
+
+/*
+/ the first check of a monitor has nothing to compare with.
+/ answers are compared with the last good ones, so a changed
+/ record stays down until it's changed back, and a failed
+/ lookup in between doesn't reset what was expected
+ */
+fn answers_changed(previous: Option<Vec<String>>, answers: &[String]) -> Option<String> {
+    match previous {
+        Some(previous) if previous != answers => Some(format!("answers changed from {previous:?} to {answers:?}")),
+        _ => None,
+    }
+}
+
+async fn previous_answers(db: &PgPool, website: &Website) -> Option<Vec<String>> {
+    let previous: Option<Option<Vec<String>>> = sqlx::query_scalar(
+        "SELECT dns_answers FROM logs
+         WHERE website_id = $1 AND is_up AND dns_answers IS NOT NULL
+         ORDER BY created_at DESC LIMIT 1"
+    )
+        .bind(website.id)
+        .fetch_optional(db)
+        .await
+        .unwrap_or_else(|e| {
+            println!("Failed to fetch previous dns answers of {}: {e}", website.alias);
+            None
+        });
+
+    previous.flatten()
+}
+
+impl From<ResolveError> for CheckError {
+    fn from(e: ResolveError) -> Self {
+        let kind = match e.kind() {
+            ResolveErrorKind::Timeout => ErrorKind::Timeout,
+            _ => ErrorKind::Dns,
+        };
+
+        Self {
+            kind,
+            message: e.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use hickory_resolver::proto::op::{Message, MessageType, ResponseCode};
+    use hickory_resolver::proto::rr::rdata::{A, AAAA, TXT};
+    use hickory_resolver::proto::rr::{Name, RData, Record};
+    use hickory_resolver::proto::serialize::binary::{BinDecodable, BinEncodable};
+    use tokio::net::UdpSocket;
+
+    use super::*;
+
+    /*
+    / a resolver on a random local port that answers every
+    / query with the given records and response code
+     */
+    async fn stub_resolver(response_code: ResponseCode, answers: Vec<Record>) -> String {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut buffer = [0; 512];
+            while let Ok((len, from)) = socket.recv_from(&mut buffer).await {
+                let query = Message::from_bytes(&buffer[..len]).unwrap();
+
+                let mut response = Message::new();
+                response
+                    .set_id(query.id())
+                    .set_message_type(MessageType::Response)
+                    .set_op_code(query.op_code())
+                    .set_recursion_desired(query.recursion_desired())
+                    .set_recursion_available(true)
+                    .set_response_code(response_code);
+                response.add_queries(query.queries().to_vec());
+                response.add_answers(answers.clone());
+
+                socket.send_to(&response.to_bytes().unwrap(), from).await.unwrap();
+            }
+        });
+
+        address.to_string()
+    }
+
+    fn record(rdata: RData) -> Record {
+        Record::from_rdata(Name::from_ascii("example.com.").unwrap(), 60, rdata)
+    }
+
+    #[tokio::test]
+    async fn resolves_sorted_answers_of_the_record_type() {
+        let address = stub_resolver(ResponseCode::NoError, vec![
+            record(RData::A(A::new(203, 0, 113, 2))),
+            record(RData::A(A::new(203, 0, 113, 1))),
+            record(RData::A(A::new(203, 0, 113, 2))),
+            record(RData::AAAA(AAAA::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))),
+        ]).await;
+
+        let resolver = resolver(Some(&address), Duration::from_secs(5)).unwrap();
+        let answers = resolve(&resolver, "example.com", RecordType::A).await.unwrap();
+
+        assert_eq!(answers, ["203.0.113.1", "203.0.113.2"]);
+    }
+
+    #[tokio::test]
+    async fn normalizes_txt_answers() {
+        let address = stub_resolver(ResponseCode::NoError, vec![
+            record(RData::TXT(TXT::new(vec!["V=spf1 -all".to_owned()]))),
+        ]).await;
+
+        let resolver = resolver(Some(&address), Duration::from_secs(5)).unwrap();
+        let answers = resolve(&resolver, "example.com", RecordType::TXT).await.unwrap();
+
+        assert_eq!(answers, ["v=spf1 -all"]);
+    }
+
+    #[tokio::test]
+    async fn missing_names_are_dns_errors() {
+        let address = stub_resolver(ResponseCode::NXDomain, vec![]).await;
+
+        let resolver = resolver(Some(&address), Duration::from_secs(5)).unwrap();
+        let error: CheckError = resolve(&resolver, "example.com", RecordType::A).await.unwrap_err().into();
+
+        assert_eq!(error.kind, ErrorKind::Dns);
+    }
+
+    #[test]
+    fn parses_targets_and_resolver_addresses() {
+        assert_eq!(target("dns://example.com").unwrap(), "example.com");
+        assert!(target("https://example.com").is_err());
+
+        assert_eq!(resolver_address("1.1.1.1").unwrap(), SocketAddr::new(Ipv4Addr::new(1, 1, 1, 1).into(), 53));
+        assert_eq!(resolver_address("127.0.0.1:5353").unwrap().port(), 5353);
+        assert!(resolver_address("resolver.example.com").is_err());
+    }
+
+    #[test]
+    fn matches_expected_answers_as_a_set() {
+        let answers = vec!["mail.example.com".to_owned(), "v=spf1 -all".to_owned()];
+
+        assert_eq!(expected_mismatch("\"V=spf1 -all\", mail.example.com., ", &answers), None);
+        assert!(expected_mismatch("mail.example.com", &answers).is_some());
+    }
+
+    #[test]
+    fn reports_changed_answers() {
+        let answers = vec!["203.0.113.1".to_owned()];
+
+        assert_eq!(answers_changed(None, &answers), None);
+        assert_eq!(answers_changed(Some(answers.clone()), &answers), None);
+        assert_eq!(
+            answers_changed(Some(vec!["203.0.113.2".to_owned()]), &answers).unwrap(),
+            "answers changed from [\"203.0.113.2\"] to [\"203.0.113.1\"]"
+        );
+    }
+
+    #[test]
+    fn changed_answers_stay_down_until_changed_back() {
+        let good = vec!["203.0.113.1".to_owned()];
+        let hijacked = vec!["198.51.100.7".to_owned()];
+
+        // logs of consecutive checks as (is_up, dns_answers), newest last,
+        // and the baseline picked the way previous_answers picks it
+        let mut logs: Vec<(bool, Option<Vec<String>>)> = vec![(true, Some(good.clone()))];
+        let mut check = |answers: Option<&Vec<String>>| {
+            let previous = logs
+                .iter()
+                .rev()
+                .find(|(is_up, answers)| *is_up && answers.is_some())
+                .and_then(|(_, answers)| answers.clone());
+            let failed = answers.and_then(|answers| answers_changed(previous, answers));
+            let is_up = answers.is_some() && failed.is_none();
+            logs.push((is_up, answers.cloned()));
+            is_up
+        };
+
+        assert!(!check(Some(&hijacked)));
+        assert!(!check(Some(&hijacked)));
+        assert!(!check(None));
+        assert!(!check(Some(&hijacked)));
+        assert!(check(Some(&good)));
+    }
+}
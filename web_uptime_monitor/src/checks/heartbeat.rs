@@ -0,0 +1,109 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use super::{CheckError, CheckOutcome, ErrorKind};
+use crate::Website;
+
+/*
+/ heartbeat monitors aren't polled, the job they watch pings
+/ POST /heartbeat/:token instead. every check interval we look
+/ at when the last ping (or finish) arrived and count the job
+/ as down if it's later than the interval plus the grace time
+ */
+pub async fn check(db: &PgPool, website: &Website) -> CheckOutcome {
+    let last_ping: Option<DateTime<Utc>> = match sqlx::query_scalar(
+        "SELECT max(received_at) FROM heartbeats WHERE website_id = $1 AND kind IN ('ping', 'finish')"
+    )
+        .bind(website.id)
+        .fetch_one(db)
+        .await {
+        Ok(last_ping) => last_ping,
+        Err(e) => {
+            let error = CheckError {
+                kind: ErrorKind::Database,
+                message: format!("couldn't look up the last heartbeat: {e}"),
+            };
+            return CheckOutcome::failed(error, None, None);
+        }
+    };
+
+    let resumed_at: Option<DateTime<Utc>> = sqlx::query_scalar(
+        "SELECT max(resumed_at) FROM website_pauses WHERE website_id = $1"
+    )
+        .bind(website.id)
+        .fetch_one(db)
+        .await
+        .unwrap_or(None);
+
+    let failed_assertion = silence(
+        website.created_at,
+        last_ping,
+        resumed_at,
+        website.check_interval_secs,
+        website.heartbeat_grace_secs,
+        Utc::now(),
+    );
+
+    CheckOutcome {
+        is_up: failed_assertion.is_none(),
+        status: None,
+        error: None,
+        failed_assertion,
+        json_values: None,
+        dns_answers: None,
+        timings: None,
+        certificate: None,
+        certificate_warning: None,
+        attempts: Vec::new(),
+    }
+}
+
+/*
+/ a monitor that never got a ping gets one period of grace from
+/ when it was created, and one that was paused gets one from when
+/ it was resumed. the message says which of those it's counted from
+ */
+fn silence(
+    created_at: DateTime<Utc>,
+    last_ping: Option<DateTime<Utc>>,
+    resumed_at: Option<DateTime<Utc>>,
+    interval_secs: i32,
+    grace_secs: i32,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    let (since, event) = [(last_ping, "the last one"), (resumed_at, "the monitor was resumed")]
+        .into_iter()
+        .filter_map(|(at, event)| at.map(|at| (at, event)))
+        .max_by_key(|(at, _)| *at)
+        .unwrap_or((created_at, "the monitor was created"));
+
+    let silent_secs = (now - since).num_seconds();
+
+    (silent_secs > (interval_secs + grace_secs) as i64).then(|| format!(
+        "no heartbeat since {event} at {since} ({silent_secs}s ago), expected one every {interval_secs}s (+{grace_secs}s grace)"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn counts_silence_from_the_latest_of_ping_resume_and_creation() {
+        let now = Utc::now();
+        let created_at = now - Duration::hours(1);
+        let silence = |last_ping, resumed_at| silence(created_at, last_ping, resumed_at, 60, 30, now);
+
+        assert!(silence(None, None).unwrap().starts_with("no heartbeat since the monitor was created"));
+        assert!(silence(None, Some(now - Duration::minutes(5))).unwrap().starts_with("no heartbeat since the monitor was resumed"));
+        assert!(silence(Some(now - Duration::minutes(5)), None).unwrap().starts_with("no heartbeat since the last one"));
+        assert!(silence(Some(now - Duration::minutes(10)), Some(now - Duration::minutes(5)))
+            .unwrap()
+            .starts_with("no heartbeat since the monitor was resumed"));
+
+        assert_eq!(silence(None, Some(now - Duration::seconds(60))), None);
+        assert_eq!(silence(Some(now - Duration::seconds(90)), None), None);
+    }
+}
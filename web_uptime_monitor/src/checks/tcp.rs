@@ -0,0 +1,169 @@
+use reqwest::Url;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{self, Duration, Instant};
+
+use super::{elapsed_ms, CheckError, CheckOutcome, ErrorKind, Timings};
+use crate::Website;
+
+/*
+/ the most we read while waiting for the expected banner
+ */
+const MAX_BANNER_BYTES: usize = 8192;
+
+/*
+/ a tcp monitor connects to tcp://host:port and, if configured,
+/ sends some bytes and waits for the expected bytes to come back.
+/ without an expected banner a successful connect counts as up
+ */
+pub async fn check(website: &Website) -> CheckOutcome {
+    let timeout = Duration::from_secs(website.timeout_secs as u64);
+    let started = Instant::now();
+
+    match time::timeout(timeout, probe(website, started)).await {
+        Ok(outcome) => outcome,
+        Err(_) => {
+            let error = CheckError {
+                kind: ErrorKind::Timeout,
+                message: format!("no answer within {}s", website.timeout_secs),
+            };
+            CheckOutcome::failed(error, None, None)
+        }
+    }
+}
+
+async fn probe(website: &Website, started: Instant) -> CheckOutcome {
+    let (host, port) = match target(&website.url) {
+        Ok(target) => target,
+        Err(message) => return CheckOutcome::failed(CheckError { kind: ErrorKind::Config, message }, None, None),
+    };
+
+    let mut stream = match TcpStream::connect((host.as_str(), port)).await {
+        Ok(stream) => stream,
+        Err(e) => return CheckOutcome::failed(e.into(), None, None),
+    };
+
+    let connect_ms = elapsed_ms(started);
+    let timings = || Timings {
+        ttfb_ms: connect_ms,
+        response_time_ms: elapsed_ms(started),
+    };
+
+    if let Some(send) = &website.tcp_send {
+        if let Err(e) = stream.write_all(&unescape(send)).await {
+            return CheckOutcome::failed(e.into(), None, Some(timings()));
+        }
+    }
+
+    let failed_assertion = match &website.tcp_expect {
+        Some(expected) => match read_until(&mut stream, &unescape(expected)).await {
+            Ok(failed_assertion) => failed_assertion,
+            Err(e) => {
+                let mut error = CheckError::from(e);
+                error.kind = ErrorKind::BodyRead;
+                return CheckOutcome::failed(error, None, Some(timings()));
+            }
+        },
+        None => None,
+    };
+
+    CheckOutcome {
+        is_up: failed_assertion.is_none(),
+        status: None,
+        error: None,
+        failed_assertion,
+        json_values: None,
+        dns_answers: None,
+        timings: Some(timings()),
+        certificate: None,
+        certificate_warning: None,
+        attempts: Vec::new(),
+    }
+}
+
+pub fn target(url: &str) -> Result<(String, u16), String> {
+    let url = Url::parse(url).map_err(|e| format!("{url:?} is not a valid tcp target: {e}"))?;
+
+    match (url.scheme(), url.host_str(), url.port()) {
+        ("tcp", Some(host), Some(port)) => Ok((host.trim_matches(['[', ']']).to_owned(), port)),
+        _ => Err(format!("{url} should look like tcp://host:port")),
+    }
+}
+
+/*
+/ keep reading until the expected bytes show up, the peer
+/ closes the connection or we've read more than we care to
+ */
+async fn read_until(stream: &mut TcpStream, expected: &[u8]) -> std::io::Result<Option<String>> {
+    let mut received = Vec::new();
+    let mut buf = [0; 1024];
+
+    loop {
+        if expected.is_empty() || received.windows(expected.len()).any(|window| window == expected) {
+            return Ok(None);
+        }
+
+        if received.len() >= MAX_BANNER_BYTES {
+            break;
+        }
+
+        match stream.read(&mut buf).await? {
+            0 => break,
+            read => received.extend_from_slice(&buf[..read]),
+        }
+    }
+
+    Ok(Some(format!(
+        "expected {:?}, received {:?}",
+        String::from_utf8_lossy(expected),
+        String::from_utf8_lossy(&received)
+    )))
+}
+
+/*
+/ the send and expect fields are typed into a form, so we
+/ allow \r, \n, \t and \\ escapes for protocols like SMTP
+ */
+fn unescape(value: &str) -> Vec<u8> {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescapes_control_characters() {
+        assert_eq!(unescape(r"PING\r\n"), b"PING\r\n");
+        assert_eq!(unescape(r"a\tb"), b"a\tb");
+        assert_eq!(unescape(r"back\\slash"), br"back\slash");
+        assert_eq!(unescape(r"\x"), b"x");
+        assert_eq!(unescape(r"trailing\"), br"trailing\");
+    }
+
+    #[test]
+    fn parses_targets() {
+        assert_eq!(target("tcp://db.example.com:5432").unwrap(), ("db.example.com".to_owned(), 5432));
+        assert_eq!(target("tcp://[::1]:6379").unwrap(), ("::1".to_owned(), 6379));
+        assert!(target("tcp://db.example.com").is_err());
+        assert!(target("https://db.example.com:443").is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use shuttle_runtime::SecretStore;
+
+const NONCE_LEN: usize = 12;
+
+/*
+/ what the key links are signed with is derived for, so
+/ signatures aren't MACs under the encryption key itself
+ */
+const SIGNING_KEY_INFO: &[u8] = b"shuttle status monitor link signing";
+
+/*
+/ encrypts monitor secrets (auth credentials, custom headers)
+/ before they go into postgres. the key is a base64 encoded
+/ 32 byte value stored as MONITOR_SECRETS_KEY in Secrets.toml,
+/ every value is stored as its random nonce followed by the
+/ AES-256-GCM ciphertext. links, like the acknowledge links
+/ sent out in notifications, are signed with a separate key
+/ derived from it
+ */
+#[derive(Clone)]
+pub struct SecretCipher {
+    cipher: Aes256Gcm,
+    signing_key: Vec<u8>,
+}
+
+impl SecretCipher {
+    pub fn from_secrets(secrets: &SecretStore) -> Option<Self> {
+        let key = secrets.get("MONITOR_SECRETS_KEY")?;
+
+        match BASE64.decode(key.trim()) {
+            Ok(key) if key.len() == 32 => Some(Self::new(&key)),
+            _ => {
+                println!("MONITOR_SECRETS_KEY must be 32 bytes encoded as base64, monitor secrets are disabled");
+                None
+            }
+        }
+    }
+
+    fn new(key: &[u8]) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+            signing_key: derive_key(key, SIGNING_KEY_INFO),
+        }
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Vec<u8> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .expect("encrypting into a Vec can't run out of space");
+
+        let mut encrypted = nonce.to_vec();
+        encrypted.extend(ciphertext);
+        encrypted
+    }
+
+    pub fn decrypt(&self, encrypted: &[u8]) -> Result<String, String> {
+        if encrypted.len() < NONCE_LEN {
+            return Err("encrypted value is too short".to_owned());
+        }
+
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "couldn't decrypt value, was MONITOR_SECRETS_KEY changed?".to_owned())?;
+
+        String::from_utf8(plaintext).map_err(|_| "decrypted value is not valid UTF-8".to_owned())
+    }
+
+    /*
+    / hex encoded HMAC-SHA256 of a message
+     */
+    pub fn sign(&self, message: &str) -> String {
+        hex::encode(self.mac(message).finalize().into_bytes())
+    }
+
+    pub fn verify(&self, message: &str, signature: &str) -> bool {
+        match hex::decode(signature) {
+            Ok(signature) => self.mac(message).verify_slice(&signature).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn mac(&self, message: &str) -> Hmac<Sha256> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.signing_key).expect("hmac accepts keys of any length");
+        mac.update(message.as_bytes());
+        mac
+    }
+}
+
+/*
+/ a 32 byte key for one purpose, the single block HKDF-Expand
+/ of the master key (which is already uniformly random, so
+/ there's nothing to extract) with that purpose as its info
+ */
+fn derive_key(key: &[u8], info: &[u8]) -> Vec<u8> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("hmac accepts keys of any length");
+    mac.update(info);
+    mac.update(&[1]);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::incidents::Incident;
+
+    use super::*;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    #[test]
+    fn decrypts_what_it_encrypted() {
+        let cipher = SecretCipher::new(&KEY);
+
+        let first = cipher.encrypt("Authorization: Bearer hunter2");
+        let second = cipher.encrypt("Authorization: Bearer hunter2");
+
+        assert_ne!(first, second, "every value gets its own nonce");
+        assert_eq!(cipher.decrypt(&first).unwrap(), "Authorization: Bearer hunter2");
+        assert_eq!(cipher.decrypt(&second).unwrap(), "Authorization: Bearer hunter2");
+    }
+
+    #[test]
+    fn refuses_tampered_truncated_or_foreign_values() {
+        let cipher = SecretCipher::new(&KEY);
+        let encrypted = cipher.encrypt("hunter2");
+
+        let mut tampered = encrypted.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(cipher.decrypt(&tampered).is_err());
+
+        assert!(cipher.decrypt(&encrypted[..encrypted.len() - 1]).is_err());
+        assert!(cipher.decrypt(&encrypted[..NONCE_LEN - 1]).is_err());
+        assert!(SecretCipher::new(&[8; 32]).decrypt(&encrypted).is_err());
+    }
+
+    #[test]
+    fn signatures_only_verify_for_their_own_incident() {
+        let cipher = SecretCipher::new(&KEY);
+        let signature = cipher.sign(&Incident::acknowledge_message(41));
+
+        assert!(cipher.verify(&Incident::acknowledge_message(41), &signature));
+        assert!(!cipher.verify(&Incident::acknowledge_message(42), &signature));
+        assert!(!cipher.verify(&Incident::acknowledge_message(41), "not hex"));
+        assert!(!SecretCipher::new(&[8; 32]).verify(&Incident::acknowledge_message(41), &signature));
+    }
+
+    #[test]
+    fn signs_with_a_key_derived_from_the_encryption_key() {
+        let cipher = SecretCipher::new(&KEY);
+        let message = Incident::acknowledge_message(41);
+
+        let mut under_encryption_key = <Hmac<Sha256> as Mac>::new_from_slice(&KEY).unwrap();
+        under_encryption_key.update(message.as_bytes());
+
+        assert_ne!(cipher.signing_key, KEY);
+        assert_eq!(cipher.signing_key.len(), 32);
+        assert_ne!(cipher.sign(&message), hex::encode(under_encryption_key.finalize().into_bytes()));
+    }
+}
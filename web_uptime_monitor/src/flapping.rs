@@ -0,0 +1,115 @@
+use sqlx::PgPool;
+
+use crate::Website;
+
+/*
+/ what a check did to the flapping state of its monitor
+ */
+pub enum FlapChange {
+    Started { state_changes: i64, window_mins: i32 },
+    Stopped { is_up: bool },
+}
+
+/*
+/ a monitor is flapping when it went up and down at least
+/ state_changes times within the last window_mins minutes.
+/ it only counts as stable again once it changed state less
+/ than half as often, so a monitor right at the threshold
+/ doesn't flip between flapping and stable on every check
+ */
+pub struct FlapDetector {
+    db: PgPool,
+    window_mins: i32,
+    state_changes: i64,
+}
+
+impl FlapDetector {
+    pub fn new(db: PgPool, window_mins: usize, state_changes: usize) -> Self {
+        Self {
+            db,
+            window_mins: window_mins.try_into().unwrap_or(i32::MAX),
+            state_changes: state_changes.try_into().unwrap_or(i64::MAX),
+        }
+    }
+
+    pub async fn update(&self, website: &Website) -> Result<Option<FlapChange>, sqlx::Error> {
+        let state_changes: i64 = sqlx::query_scalar(
+            "SELECT count(*) FROM ( \
+                SELECT is_up, lag(is_up) OVER (ORDER BY created_at) AS was_up FROM logs \
+                WHERE website_id = $1 AND NOT in_maintenance \
+                AND created_at > current_timestamp - make_interval(mins => $2) \
+            ) checks WHERE is_up <> was_up"
+        )
+            .bind(website.id)
+            .bind(self.window_mins)
+            .fetch_one(&self.db)
+            .await?;
+
+        // only the check that actually changes the flag reports the change
+        match is_flapping(state_changes, self.state_changes) {
+            Some(true) => self.start(website, state_changes).await,
+            Some(false) => self.stop(website).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn start(&self, website: &Website, state_changes: i64) -> Result<Option<FlapChange>, sqlx::Error> {
+        let started: Option<i32> = sqlx::query_scalar(
+            "UPDATE websites SET flapping_since = current_timestamp \
+            WHERE id = $1 AND flapping_since IS NULL RETURNING id"
+        )
+            .bind(website.id)
+            .fetch_optional(&self.db)
+            .await?;
+
+        Ok(started.map(|_| FlapChange::Started {
+            state_changes,
+            window_mins: self.window_mins,
+        }))
+    }
+
+    async fn stop(&self, website: &Website) -> Result<Option<FlapChange>, sqlx::Error> {
+        let stopped: Option<Option<bool>> = sqlx::query_scalar(
+            "UPDATE websites SET flapping_since = NULL \
+            WHERE id = $1 AND flapping_since IS NOT NULL \
+            RETURNING (SELECT is_up FROM logs WHERE website_id = $1 ORDER BY created_at DESC LIMIT 1)"
+        )
+            .bind(website.id)
+            .fetch_optional(&self.db)
+            .await?;
+
+        Ok(stopped.map(|is_up| FlapChange::Stopped {
+            is_up: is_up.unwrap_or(false),
+        }))
+    }
+}
+
+/*
+/ whether a monitor that changed state this often within the
+/ window is flapping, or None while it's between the two
+/ thresholds and stays however it was
+ */
+fn is_flapping(state_changes: i64, threshold: i64) -> Option<bool> {
+    if state_changes >= threshold {
+        Some(true)
+    } else if state_changes < threshold / 2 {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flaps_at_the_threshold_and_settles_below_half_of_it() {
+        assert_eq!(is_flapping(6, 6), Some(true));
+        assert_eq!(is_flapping(9, 6), Some(true));
+        assert_eq!(is_flapping(5, 6), None);
+        assert_eq!(is_flapping(3, 6), None);
+        assert_eq!(is_flapping(2, 6), Some(false));
+        assert_eq!(is_flapping(0, 6), Some(false));
+    }
+}
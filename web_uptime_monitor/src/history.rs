@@ -0,0 +1,95 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{PgConnection, PgPool};
+
+use crate::Website;
+
+/*
+/ state the checker keeps on a monitor rather than settings
+/ someone changed, these aren't part of its history
+ */
+const UNTRACKED_FIELDS: [&str; 4] = ["id", "created_at", "flapping_since", "paused_at"];
+
+/*
+/ a setting of a monitor that was changed by editing it.
+/ secrets are encrypted so only the fact that they changed
+/ is kept, not their values
+ */
+#[derive(sqlx::FromRow, Serialize)]
+pub struct WebsiteChange {
+    pub id: i32,
+    pub website_id: i32,
+    pub changed_at: DateTime<Utc>,
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+impl WebsiteChange {
+    pub async fn for_website(db: &PgPool, website_id: i32) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            "SELECT * FROM website_changes WHERE website_id = $1 ORDER BY changed_at DESC, id LIMIT 50"
+        )
+            .bind(website_id)
+            .fetch_all(db)
+            .await
+    }
+
+    /*
+    / compare a monitor before and after it was edited and
+    / store every setting that changed, along with the names
+    / of the secrets that changed. it takes the connection of
+    / the transaction that saves the monitor, so the history
+    / can't miss an edit or record one that was rolled back
+     */
+    pub async fn record(
+        conn: &mut PgConnection,
+        old: &Website,
+        new: &Website,
+        changed_secrets: &[&str],
+    ) -> Result<(), sqlx::Error> {
+        let (Ok(Value::Object(old_fields)), Ok(Value::Object(new_fields))) =
+            (serde_json::to_value(old), serde_json::to_value(new)) else {
+            return Ok(());
+        };
+
+        let changes = new_fields
+            .iter()
+            .filter(|(field, _)| !UNTRACKED_FIELDS.contains(&field.as_str()))
+            .filter(|(field, value)| old_fields.get(*field) != Some(value))
+            .map(|(field, value)| (field.as_str(), old_fields.get(field).and_then(as_text), as_text(value)))
+            .chain(changed_secrets.iter().map(|secret| (*secret, None, None)));
+
+        for (field, old_value, new_value) in changes {
+            sqlx::query(
+                "INSERT INTO website_changes (website_id, field, old_value, new_value) VALUES ($1, $2, $3, $4)"
+            )
+                .bind(new.id)
+                .bind(field)
+                .bind(old_value)
+                .bind(new_value)
+                .execute(&mut *conn)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub fn describe(&self) -> String {
+        match (&self.old_value, &self.new_value) {
+            (None, None) => format!("{} changed", self.field),
+            (Some(old_value), None) => format!("{} {old_value:?} removed", self.field),
+            (None, Some(new_value)) => format!("{} set to {new_value:?}", self.field),
+            (Some(old_value), Some(new_value)) => format!("{} changed from {old_value:?} to {new_value:?}", self.field),
+        }
+    }
+}
+
+fn as_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(text) => Some(text.clone()),
+        other => Some(other.to_string()),
+    }
+}
@@ -0,0 +1,213 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use utoipa::ToSchema;
+
+use crate::checks::CheckOutcome;
+use crate::Website;
+
+/*
+/ an outage of a monitor, from the first failed check until
+/ the first check that's up again, or a certificate that's
+/ about to expire, from the first check that saw it until
+/ one that saw a certificate with enough time left. the
+/ checker opens and resolves incidents as monitors change
+/ state, so a website can have at most one open incident of
+/ each kind at a time. acknowledging an open incident stops
+/ it from being escalated any further
+ */
+#[derive(sqlx::FromRow, Serialize, ToSchema)]
+pub struct Incident {
+    pub id: i32,
+    pub website_id: i32,
+    pub kind: IncidentKind,
+    pub opened_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub cause: String,
+    pub first_status: Option<i16>,
+    pub last_status: Option<i16>,
+    pub failed_checks: i32,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+}
+
+#[derive(sqlx::Type, Serialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum IncidentKind {
+    Down,
+    Certificate,
+}
+
+/*
+/ what a recorded check did to the incidents of its monitor
+ */
+pub enum Transition {
+    Opened(Incident),
+    Resolved(Incident),
+}
+
+impl Incident {
+    pub async fn for_website(db: &PgPool, website_id: i32) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            "SELECT * FROM incidents WHERE website_id = $1 ORDER BY opened_at DESC LIMIT 50"
+        )
+            .bind(website_id)
+            .fetch_all(db)
+            .await
+    }
+
+    pub async fn by_id<'e>(db: impl PgExecutor<'e>, id: i32) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM incidents WHERE id = $1")
+            .bind(id)
+            .fetch_optional(db)
+            .await
+    }
+
+    /*
+    / acknowledging twice keeps the time of the first one
+     */
+    pub async fn acknowledge(db: &PgPool, id: i32) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE incidents SET acknowledged_at = coalesce(acknowledged_at, current_timestamp) WHERE id = $1"
+        )
+            .bind(id)
+            .execute(db)
+            .await?;
+
+        Ok(())
+    }
+
+    /*
+    / what the acknowledge links in notifications are signed over
+     */
+    pub fn acknowledge_message(id: i32) -> String {
+        format!("acknowledge incident {id}")
+    }
+
+    pub fn is_certificate(&self) -> bool {
+        self.kind == IncidentKind::Certificate
+    }
+
+    /*
+    / open incidents last until now
+     */
+    pub fn duration(&self) -> String {
+        format_duration((self.resolved_at.unwrap_or_else(Utc::now) - self.opened_at).num_seconds())
+    }
+
+    /*
+    / update the incidents of a website with the outcome of its
+    / latest check, opening one when it goes down and resolving
+    / the open one when it comes back up
+     */
+    pub async fn track(db: &PgPool, website: &Website, outcome: &CheckOutcome) -> Result<Option<Transition>, sqlx::Error> {
+        let open: Option<i32> = sqlx::query_scalar(
+            "SELECT id FROM incidents WHERE website_id = $1 AND kind = 'down' AND resolved_at IS NULL"
+        )
+            .bind(website.id)
+            .fetch_optional(db)
+            .await?;
+
+        match (open, outcome.is_up) {
+            (None, false) => {
+                let incident = sqlx::query_as::<_, Self>(
+                    "INSERT INTO incidents (website_id, cause, first_status, last_status) \
+                    VALUES ($1, $2, $3, $3) RETURNING *"
+                )
+                    .bind(website.id)
+                    .bind(cause(outcome))
+                    .bind(outcome.status)
+                    .fetch_one(db)
+                    .await?;
+
+                Ok(Some(Transition::Opened(incident)))
+            }
+            (Some(id), false) => {
+                sqlx::query(
+                    "UPDATE incidents SET last_status = $2, failed_checks = failed_checks + 1 WHERE id = $1"
+                )
+                    .bind(id)
+                    .bind(outcome.status)
+                    .execute(db)
+                    .await?;
+
+                Ok(None)
+            }
+            (Some(id), true) => {
+                let incident = sqlx::query_as::<_, Self>(
+                    "UPDATE incidents SET resolved_at = current_timestamp WHERE id = $1 RETURNING *"
+                )
+                    .bind(id)
+                    .fetch_one(db)
+                    .await?;
+
+                Ok(Some(Transition::Resolved(incident)))
+            }
+            (None, true) => Ok(None),
+        }
+    }
+
+    /*
+    / update the certificate incident of a website with the
+    / warning about its certificate from the latest check that
+    / got one, opening one when the certificate is about to
+    / expire and resolving it once it's been renewed
+     */
+    pub async fn track_certificate(db: &PgPool, website: &Website, warning: Option<&str>)
+        -> Result<Option<Transition>, sqlx::Error> {
+        let open: Option<i32> = sqlx::query_scalar(
+            "SELECT id FROM incidents WHERE website_id = $1 AND kind = 'certificate' AND resolved_at IS NULL"
+        )
+            .bind(website.id)
+            .fetch_optional(db)
+            .await?;
+
+        match (open, warning) {
+            (None, Some(warning)) => {
+                let incident = sqlx::query_as::<_, Self>(
+                    "INSERT INTO incidents (website_id, kind, cause) VALUES ($1, 'certificate', $2) RETURNING *"
+                )
+                    .bind(website.id)
+                    .bind(warning)
+                    .fetch_one(db)
+                    .await?;
+
+                Ok(Some(Transition::Opened(incident)))
+            }
+            (Some(id), None) => {
+                let incident = sqlx::query_as::<_, Self>(
+                    "UPDATE incidents SET resolved_at = current_timestamp WHERE id = $1 RETURNING *"
+                )
+                    .bind(id)
+                    .fetch_one(db)
+                    .await?;
+
+                Ok(Some(Transition::Resolved(incident)))
+            }
+            (Some(_), Some(_)) | (None, None) => Ok(None),
+        }
+    }
+}
+
+pub fn format_duration(seconds: i64) -> String {
+    match seconds {
+        s if s < 60 => format!("{s}s"),
+        s if s < 3600 => format!("{}m {}s", s / 60, s % 60),
+        s if s < 86400 => format!("{}h {}m", s / 3600, s % 3600 / 60),
+        s => format!("{}d {}h", s / 86400, s % 86400 / 3600),
+    }
+}
+
+/*
+/ a failed check either never got a response at all, came
+/ back with a bad status code or failed one of the assertions
+ */
+fn cause(outcome: &CheckOutcome) -> String {
+    match (&outcome.error, &outcome.failed_assertion, outcome.status) {
+        (Some(error), _, _) => format!("{}: {}", error.kind.as_str(), error.message),
+        (None, Some(assertion), Some(status)) => format!("{status}: {assertion}"),
+        (None, Some(assertion), None) => assertion.clone(),
+        (None, None, Some(status)) => format!("status {status}"),
+        (None, None, None) => "unknown failure".to_owned(),
+    }
+}
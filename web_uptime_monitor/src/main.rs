@@ -1,21 +1,53 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
 use askama::Template;
 use chrono::Timelike;
 use askama_axum::IntoResponse as AskamaIntoResponse;
 use axum::{
-    extract::{Form, Path, State},
+    extract::{Form, Json, Path, Query, State},
     http::StatusCode,
     response::{IntoResponse as AxumIntoResponse, Redirect, Response},
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
-use chrono::{DateTime, Utc};
-use futures_util::StreamExt;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use shuttle_runtime::SecretStore;
 use sqlx::PgPool;
 use sqlx::postgres::any::AnyConnectionBackend;
-use tokio::time::{self, Duration};
-use validator::Validate;
+use utoipa::{OpenApi, ToSchema};
+use utoipa_swagger_ui::SwaggerUi;
+use validator::{Validate, ValidateUrl, ValidationError};
+
+use crate::api::ApiDoc;
+use crate::assertions::JsonAssertion;
+use crate::certificates::Certificate;
+use crate::checks::{
+    dns_target, parse_headers, resolver_address, tcp_target, AcceptedStatuses, DNS_RECORD_TYPES, HTTP_METHODS,
+};
+use crate::crypto::SecretCipher;
+use crate::history::WebsiteChange;
+use crate::incidents::Incident;
+use crate::maintenance::{parse_cron, MaintenanceWindow, MAX_DURATION_MINS};
+use crate::notifications::{email_recipients, ChannelKind, Delivery, LinkedChannel, Mailer, NotificationChannel, Notifier};
+use crate::scheduler::{Scheduler, SchedulerConfig};
+
+mod api;
+mod assertions;
+mod certificates;
+mod checks;
+mod crypto;
+mod flapping;
+mod history;
+mod incidents;
+mod maintenance;
+mod notifications;
+mod scheduler;
 
 
 /*
@@ -26,6 +58,8 @@ struct WebsiteInfo {
     #[validate(url)]
     url: String,
     alias: String,
+    flapping_since: Option<DateTime<Utc>>,
+    paused_at: Option<DateTime<Utc>>,
     data: Vec<WebsiteStats>,
 }
 
@@ -35,31 +69,127 @@ struct WebsiteLogs {
     logs: Vec<WebsiteInfo>
 }
 
-#[derive(sqlx::FromRow, Serialize)]
+#[derive(sqlx::FromRow, Serialize, ToSchema)]
 pub struct WebsiteStats {
     time: DateTime<Utc>,
     uptime_pct: Option<i16>,
+    avg_response_ms: Option<i32>,
+    p95_response_ms: Option<i32>,
+    p99_response_ms: Option<i32>,
+    maintenance_pct: Option<i16>,
+    #[sqlx(default)]
+    paused: bool,
+}
+
+impl WebsiteStats {
+    fn latency(&self) -> String {
+        match (self.avg_response_ms, self.p95_response_ms, self.p99_response_ms) {
+            (Some(avg), Some(p95), Some(p99)) => format!("avg {avg}ms, p95 {p95}ms, p99 {p99}ms"),
+            _ => String::new(),
+        }
+    }
+
+    fn maintenance(&self) -> String {
+        match self.maintenance_pct {
+            Some(pct) if pct > 0 => format!("({pct}% of checks during maintenance, not counted)"),
+            _ => String::new(),
+        }
+    }
 }
 
 #[derive(Serialize, sqlx::FromRow, Template)]
 #[template(path = "single_website.html")]
 struct SingleWebsiteLogs {
     log: WebsiteInfo,
+    website: Website,
+    certificate: Option<Certificate>,
     incidents: Vec<Incident>,
+    job_runs: Vec<JobRun>,
+    channels: Vec<LinkedChannel>,
+    available_channels: Vec<NotificationChannel>,
+    maintenance_windows: Vec<MaintenanceWindow>,
+    changes: Vec<WebsiteChange>,
     monthly_data: Vec<WebsiteStats>,
+    acknowledge_signatures: HashMap<i32, String>,
+}
+
+impl SingleWebsiteLogs {
+    /*
+    / the acknowledge button posts the same signature as the
+    / links in notifications, there's none without a secrets key
+     */
+    fn acknowledge_signature(&self, incident: &Incident) -> Option<&str> {
+        self.acknowledge_signatures.get(&incident.id).map(String::as_str)
+    }
+}
+
+#[derive(Template)]
+#[template(path = "edit_website.html")]
+struct EditWebsitePage {
+    website: Website,
+    http_methods: &'static [&'static str],
+    dns_record_types: &'static [&'static str],
+}
+
+impl EditWebsitePage {
+    fn is_http_method(&self, http_method: &str) -> bool {
+        self.website.http_method == http_method
+    }
+
+    fn is_dns_record_type(&self, dns_record_type: &str) -> bool {
+        self.website.dns_record_type == dns_record_type
+    }
+}
+
+#[derive(Serialize, Template)]
+#[template(path = "channels.html")]
+struct ChannelsPage {
+    channels: Vec<NotificationChannel>,
+    deliveries: Vec<Delivery>,
 }
 
+#[derive(Serialize, Template)]
+#[template(path = "maintenance.html")]
+struct MaintenancePage {
+    windows: Vec<MaintenanceWindow>,
+}
+
+#[derive(Serialize, Template)]
+#[template(path = "acknowledge.html")]
+struct AcknowledgePage {
+    alias: String,
+    incident: Incident,
+    signature: String,
+}
+
+/*
+/ a finished run of a job watched by a heartbeat monitor,
+/ the duration is only known if it also sent a start ping
+ */
 #[derive(sqlx::FromRow, Serialize)]
-pub struct Incident {
-    time: DateTime<Utc>,
-    status: i16,
+pub struct JobRun {
+    received_at: DateTime<Utc>,
+    duration_ms: Option<i32>,
 }
 
 /*
-/ error handling
+/ error handling. errors are sent as json like
+/ {"error": "not_found", "message": "Not found"} so api
+/ clients can tell them apart
  */
 enum ApiError {
-    SQLError(sqlx::Error)
+    SQLError(sqlx::Error),
+    NotFound,
+    Forbidden,
+    Validation(String),
+    Conflict(String),
+}
+
+#[derive(Serialize, ToSchema)]
+struct ErrorBody {
+    #[schema(example = "not_found")]
+    error: &'static str,
+    message: String,
 }
 
 enum SplitBy {
@@ -69,88 +199,426 @@ enum SplitBy {
 
 impl From<sqlx::Error> for ApiError {
     fn from(e: sqlx::Error) -> Self {
-        Self::SQLError(e)
+        match e {
+            sqlx::Error::RowNotFound => Self::NotFound,
+            e => Self::SQLError(e),
+        }
     }
 }
 
 impl AxumIntoResponse for ApiError {
     fn into_response(self) -> Response {
-        match self {
+        let (status, error, message) = match self {
+            // what went wrong in the database stays in the logs, it can give away queries and schema
             Self::SQLError(e) => {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("SQL Error: {e}")
-                    ).into_response()
+                println!("SQL Error: {e}");
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error", "Internal error".to_owned())
             }
-        }
+            Self::NotFound => (StatusCode::NOT_FOUND, "not_found", "Not found".to_owned()),
+            Self::Forbidden => (StatusCode::FORBIDDEN, "forbidden", "Forbidden".to_owned()),
+            Self::Validation(message) => (StatusCode::UNPROCESSABLE_ENTITY, "validation_error", message),
+            Self::Conflict(message) => (StatusCode::CONFLICT, "conflict", message),
+        };
+
+        (status, Json(ErrorBody { error, message })).into_response()
+    }
+}
+
+/*
+/ aliases are unique, taking one that's in use is a conflict
+/ rather than a database failure
+ */
+fn alias_conflict(e: sqlx::Error) -> ApiError {
+    match e.as_database_error().and_then(|e| e.code()) {
+        Some(code) if code == "23505" => ApiError::Conflict("a monitor with this alias already exists".to_owned()),
+        _ => ApiError::from(e),
     }
 }
 
 /*
 / monitoring is done by fetching a list of websites from
-/ the database and sequentially sending HTTP requests to
-/ them and recording results in postgres
+/ the database and concurrently checking them and recording
+/ results in postgres, see scheduler.rs. most monitors are
+/ websites checked over http, but a monitor can also be a
+/ plain tcp connect check against tcp://host:port, a dns
+/ lookup of dns://hostname or a heartbeat that a cron job
+/ pings at /heartbeat/:token
  */
-#[derive(Deserialize, sqlx::FromRow, Validate)]
+#[derive(sqlx::Type, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Debug)]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+enum MonitorKind {
+    Http,
+    Tcp,
+    Dns,
+    Heartbeat,
+}
+
+#[derive(sqlx::FromRow, Serialize, ToSchema, Clone)]
 struct Website {
-    #[validate(url)]
+    id: i32,
+    kind: MonitorKind,
     url: String,
-    alias: String
+    alias: String,
+    check_interval_secs: i32,
+    timeout_secs: i32,
+    retries: i32,
+    confirm_failures: Option<i32>,
+    accepted_statuses: String,
+    body_contains: Option<String>,
+    body_not_contains: Option<String>,
+    body_regex: Option<String>,
+    min_body_bytes: Option<i32>,
+    max_body_bytes: Option<i32>,
+    json_assertions: Option<String>,
+    http_method: String,
+    #[serde(skip)]
+    request_headers: Option<Vec<u8>>,
+    request_body: Option<String>,
+    auth_kind: String,
+    auth_username: Option<String>,
+    #[serde(skip)]
+    auth_secret: Option<Vec<u8>>,
+    cert_expiry_days: i32,
+    tcp_send: Option<String>,
+    tcp_expect: Option<String>,
+    dns_record_type: String,
+    dns_resolver: Option<String>,
+    dns_expected: Option<String>,
+    heartbeat_token: Option<String>,
+    heartbeat_grace_secs: i32,
+    flapping_since: Option<DateTime<Utc>>,
+    paused_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
 }
 
 /*
-/ this function gathers data about our websites in the
-/ database and saves the data about websites to the
-/ logs table
+/ the form used to add a new website or edit one. the check
+/ settings are optional and fall back to the column defaults.
+/ headers and the auth secret come in as plain text and are
+/ encrypted before they're stored
  */
-async fn check_websites(db: PgPool) {
-    let mut interval = time::interval(Duration::from_secs(60));
-
-    loop {
-        interval.tick().await;
-
-        let ctx = Client::new();
-        let mut res = sqlx::query_as::<_, Website>("SELECT url, alias FROM websites").fetch(&db);
-
-        while let Some(website) = res.next().await {
-            let website = website.unwrap();
-            let response = ctx.get(website.url).send().await.unwrap();
-
-            sqlx::query(
-                "INSERT INTO logs (website_alias, status)\
-                VALUES\
-                ((SELECT id FROM websites where alias = $1), $2)"
-            )
-                .bind(website.alias)
-                .bind(response.status().as_u16() as i16)
-                .execute(&db).await
-                .unwrap();
+#[derive(Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_auth"))]
+#[validate(schema(function = "validate_target"))]
+#[validate(schema(function = "validate_confirm_failures"))]
+struct WebsiteForm {
+    #[serde(default = "default_kind")]
+    kind: MonitorKind,
+    #[serde(default)]
+    url: String,
+    #[validate(length(min = 1, max = 75))]
+    alias: String,
+    #[serde(default = "default_check_interval_secs")]
+    #[validate(range(min = 10, max = 86400))]
+    check_interval_secs: i32,
+    #[serde(default = "default_timeout_secs")]
+    #[validate(range(min = 1, max = 120))]
+    timeout_secs: i32,
+    #[serde(default)]
+    #[validate(range(min = 0, max = 10))]
+    retries: i32,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    #[validate(range(min = 1, max = 11))]
+    confirm_failures: Option<i32>,
+    #[serde(default = "default_accepted_statuses")]
+    #[validate(custom(function = "validate_accepted_statuses"))]
+    accepted_statuses: String,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    body_contains: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    body_not_contains: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    #[validate(custom(function = "validate_body_regex"))]
+    body_regex: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    #[validate(range(min = 0))]
+    min_body_bytes: Option<i32>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    #[validate(range(min = 0))]
+    max_body_bytes: Option<i32>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    #[validate(custom(function = "validate_json_assertions"))]
+    json_assertions: Option<String>,
+    #[serde(default = "default_http_method")]
+    #[validate(custom(function = "validate_http_method"))]
+    http_method: String,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    #[validate(custom(function = "validate_request_headers"))]
+    request_headers: Option<String>,
+    // editing keeps the stored headers when they're left empty, unless this is set
+    #[serde(default)]
+    clear_request_headers: bool,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    request_body: Option<String>,
+    #[serde(default = "default_auth_kind")]
+    auth_kind: String,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    auth_username: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    auth_secret: Option<String>,
+    #[serde(default = "default_cert_expiry_days")]
+    #[validate(range(min = 0, max = 365))]
+    cert_expiry_days: i32,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    tcp_send: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    tcp_expect: Option<String>,
+    #[serde(default = "default_dns_record_type")]
+    #[validate(custom(function = "validate_dns_record_type"))]
+    dns_record_type: String,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    #[validate(custom(function = "validate_dns_resolver"))]
+    dns_resolver: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    dns_expected: Option<String>,
+    #[serde(default = "default_heartbeat_grace_secs")]
+    #[validate(range(min = 0, max = 86400))]
+    heartbeat_grace_secs: i32,
+}
+
+/*
+/ html forms send empty inputs as empty strings, we want
+/ those to be None rather than a parse error. json bodies
+/ of the api can send the value itself, e.g. a number
+ */
+fn empty_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr + Deserialize<'de>,
+    T::Err: Display,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Input<T> {
+        Text(String),
+        Value(T),
+    }
+
+    match Option::<Input<T>>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Input::Value(value)) => Ok(Some(value)),
+        Some(Input::Text(text)) => match text.trim() {
+            "" => Ok(None),
+            value => value.parse().map(Some).map_err(de::Error::custom),
+        },
+    }
+}
+
+fn default_kind() -> MonitorKind {
+    MonitorKind::Http
+}
+
+fn default_check_interval_secs() -> i32 {
+    60
+}
+
+fn default_timeout_secs() -> i32 {
+    30
+}
+
+fn default_accepted_statuses() -> String {
+    "200-299".to_owned()
+}
+
+fn default_http_method() -> String {
+    "GET".to_owned()
+}
+
+fn default_auth_kind() -> String {
+    "none".to_owned()
+}
+
+fn default_cert_expiry_days() -> i32 {
+    14
+}
+
+fn default_dns_record_type() -> String {
+    "A".to_owned()
+}
+
+fn default_heartbeat_grace_secs() -> i32 {
+    60
+}
+
+fn validate_accepted_statuses(accepted_statuses: &str) -> Result<(), ValidationError> {
+    accepted_statuses
+        .parse::<AcceptedStatuses>()
+        .map(|_| ())
+        .map_err(|_| ValidationError::new("accepted_statuses"))
+}
+
+fn validate_json_assertions(json_assertions: &str) -> Result<(), ValidationError> {
+    JsonAssertion::parse_all(json_assertions)
+        .map(|_| ())
+        .map_err(|_| ValidationError::new("json_assertions"))
+}
+
+fn validate_http_method(http_method: &str) -> Result<(), ValidationError> {
+    if HTTP_METHODS.contains(&http_method) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("http_method"))
+    }
+}
+
+fn validate_request_headers(request_headers: &str) -> Result<(), ValidationError> {
+    parse_headers(request_headers)
+        .map(|_| ())
+        .map_err(|_| ValidationError::new("request_headers"))
+}
+
+fn validate_auth(form: &WebsiteForm) -> Result<(), ValidationError> {
+    match (form.auth_kind.as_str(), &form.auth_username, &form.auth_secret) {
+        ("none", _, _) | ("basic", Some(_), Some(_)) | ("bearer", _, Some(_)) => Ok(()),
+        _ => Err(ValidationError::new("auth")),
+    }
+}
+
+fn validate_target(form: &WebsiteForm) -> Result<(), ValidationError> {
+    let valid = match form.kind {
+        MonitorKind::Http => {
+            form.url.validate_url() && (form.url.starts_with("http://") || form.url.starts_with("https://"))
+        }
+        MonitorKind::Tcp => tcp_target(&form.url).is_ok(),
+        MonitorKind::Dns => dns_target(&form.url).is_ok(),
+        // heartbeats get their url generated when they're created
+        MonitorKind::Heartbeat => true,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("url"))
+    }
+}
+
+/*
+/ a failure can't need more failed attempts to be confirmed
+/ than there are attempts
+ */
+fn validate_confirm_failures(form: &WebsiteForm) -> Result<(), ValidationError> {
+    match form.confirm_failures {
+        Some(confirm_failures) if confirm_failures > form.retries + 1 => {
+            Err(ValidationError::new("confirm_failures"))
         }
+        _ => Ok(()),
+    }
+}
+
+fn validate_dns_record_type(dns_record_type: &str) -> Result<(), ValidationError> {
+    if DNS_RECORD_TYPES.contains(&dns_record_type) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("dns_record_type"))
     }
 }
 
+fn validate_dns_resolver(dns_resolver: &str) -> Result<(), ValidationError> {
+    resolver_address(dns_resolver)
+        .map(|_| ())
+        .map_err(|_| ValidationError::new("dns_resolver"))
+}
+
+fn validate_body_regex(body_regex: &str) -> Result<(), ValidationError> {
+    Regex::new(body_regex)
+        .map(|_| ())
+        .map_err(|_| ValidationError::new("body_regex"))
+}
+
 /*
 / our backend, create an initial route to add a URL
 / to monitor. we use the Validate trait to
 / automatically return an error if validation fails
  */
-async fn create_website(State(state): State<AppState>, Form(new_website): Form<Website>)
-    -> Result<impl AxumIntoResponse, impl AxumIntoResponse> {
-    if new_website.validate().is_err() {
-        return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Validation error: is your website a reachable URL?",
-        ));
+async fn create_website(State(state): State<AppState>, Form(new_website): Form<WebsiteForm>)
+    -> Result<impl AxumIntoResponse, ApiError> {
+    insert_website(&state, new_website).await?;
+
+    Ok(Redirect::to("/"))
+}
+
+/*
+/ add a monitor from the form on the main page or the api
+ */
+async fn insert_website(state: &AppState, new_website: WebsiteForm) -> Result<Website, ApiError> {
+    if let Err(e) = new_website.validate() {
+        return Err(ApiError::Validation(format!(
+            "is your website a reachable URL with sensible check settings? {e}"
+        )));
     }
 
-    sqlx::query("INSERT INTO websites (url, alias) VALUES ($1, $2)")
-        .bind(new_website.url)
+    let (request_headers, auth_secret) = encrypt_secrets(state, &new_website)?;
+
+    let (url, heartbeat_token) = match new_website.kind {
+        MonitorKind::Heartbeat => {
+            let token = heartbeat_token();
+            (format!("/heartbeat/{token}"), Some(token))
+        }
+        _ => (new_website.url, None),
+    };
+
+    let website = sqlx::query_as::<_, Website>(
+        "INSERT INTO websites \
+        (kind, url, alias, check_interval_secs, timeout_secs, retries, confirm_failures, accepted_statuses, \
+        body_contains, body_not_contains, body_regex, min_body_bytes, max_body_bytes, json_assertions, \
+        http_method, request_headers, request_body, auth_kind, auth_username, auth_secret, cert_expiry_days, \
+        tcp_send, tcp_expect, dns_record_type, dns_resolver, dns_expected, heartbeat_token, heartbeat_grace_secs) \
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, \
+        $23, $24, $25, $26, $27, $28) \
+        RETURNING *"
+    )
+        .bind(new_website.kind)
+        .bind(url)
         .bind(new_website.alias)
-        .execute(&state.db)
+        .bind(new_website.check_interval_secs)
+        .bind(new_website.timeout_secs)
+        .bind(new_website.retries)
+        .bind(new_website.confirm_failures)
+        .bind(new_website.accepted_statuses)
+        .bind(new_website.body_contains)
+        .bind(new_website.body_not_contains)
+        .bind(new_website.body_regex)
+        .bind(new_website.min_body_bytes)
+        .bind(new_website.max_body_bytes)
+        .bind(new_website.json_assertions)
+        .bind(new_website.http_method)
+        .bind(request_headers)
+        .bind(new_website.request_body)
+        .bind(new_website.auth_kind)
+        .bind(new_website.auth_username)
+        .bind(auth_secret)
+        .bind(new_website.cert_expiry_days)
+        .bind(new_website.tcp_send)
+        .bind(new_website.tcp_expect)
+        .bind(new_website.dns_record_type)
+        .bind(new_website.dns_resolver)
+        .bind(new_website.dns_expected)
+        .bind(heartbeat_token)
+        .bind(new_website.heartbeat_grace_secs)
+        .fetch_one(&state.db)
         .await
-        .unwrap();
+        .map_err(alias_conflict)?;
 
-    Ok(Redirect::to("/"))
+    Ok(website)
+}
+
+type EncryptedSecret = Option<Vec<u8>>;
+
+/*
+/ headers and the auth secret are only ever stored encrypted
+ */
+fn encrypt_secrets(state: &AppState, form: &WebsiteForm) -> Result<(EncryptedSecret, EncryptedSecret), ApiError> {
+    match &state.cipher {
+        Some(cipher) => Ok((
+            form.request_headers.as_deref().map(|headers| cipher.encrypt(headers)),
+            form.auth_secret.as_deref().map(|secret| cipher.encrypt(secret)),
+        )),
+        None if form.request_headers.is_none() && form.auth_secret.is_none() => Ok((None, None)),
+        None => Err(ApiError::Validation(
+            "MONITOR_SECRETS_KEY must be configured to store custom headers and credentials".to_owned(),
+        )),
+    }
 }
 
 /*
@@ -159,7 +627,7 @@ async fn create_website(State(state): State<AppState>, Form(new_website): Form<W
 / askama will handle that automatically for us
  */
 async fn get_websites(State(state): State<AppState>) -> Result<impl AskamaIntoResponse, ApiError> {
-    let websites = sqlx::query_as::<_, Website>("SELECT url, alias FROM websites")
+    let websites = sqlx::query_as::<_, Website>("SELECT * FROM websites")
         .fetch_all(&state.db)
         .await?;
 
@@ -171,6 +639,8 @@ async fn get_websites(State(state): State<AppState>) -> Result<impl AskamaIntoRe
         logs.push(WebsiteInfo {
             url: website.url,
             alias: website.alias,
+            flapping_since: website.flapping_since,
+            paused_at: website.paused_at,
             data,
         });
     }
@@ -180,13 +650,22 @@ async fn get_websites(State(state): State<AppState>) -> Result<impl AskamaIntoRe
 
 /*
 / function to get the daily stats of a website
-/ that's in our database
+/ that's in our database. checks run during maintenance
+/ don't count towards uptime or latency, an hour that was
+/ all maintenance has no uptime at all
  */
 async fn get_daily_stats(alias: &str, db: &PgPool) -> Result<Vec<WebsiteStats>, ApiError> {
     let data = sqlx::query_as::<_, WebsiteStats>(
         r#"
         SELECT date_trunc('hour', created_at) AS time,
-        CAST(COUNT(CASE WHEN status=200 THEN 1 END) * 100 / COUNT(*) AS int2) AS uptime_pct
+        CAST(COUNT(*) FILTER (WHERE is_up AND NOT in_maintenance) * 100
+            / NULLIF(COUNT(*) FILTER (WHERE NOT in_maintenance), 0) AS int2) AS uptime_pct,
+        CAST(AVG(response_time_ms) FILTER (WHERE NOT in_maintenance) AS int4) AS avg_response_ms,
+        CAST(percentile_cont(0.95) WITHIN GROUP (ORDER BY response_time_ms)
+            FILTER (WHERE NOT in_maintenance) AS int4) AS p95_response_ms,
+        CAST(percentile_cont(0.99) WITHIN GROUP (ORDER BY response_time_ms)
+            FILTER (WHERE NOT in_maintenance) AS int4) AS p99_response_ms,
+        CAST(COUNT(*) FILTER (WHERE in_maintenance) * 100 / COUNT(*) AS int2) AS maintenance_pct
         FROM logs
         LEFT JOIN websites ON websites.id = logs.website_id
         WHERE websites.alias = $1
@@ -201,19 +680,28 @@ async fn get_daily_stats(alias: &str, db: &PgPool) -> Result<Vec<WebsiteStats>,
     let no_of_splits = 24;
     let no_of_seconds = 3600;
     let data = fill_data_gaps(data, no_of_splits, SplitBy::Hour, no_of_seconds);
+    let data = mark_paused(data, &get_pauses(alias, db).await?, no_of_seconds);
 
     Ok(data)
 }
 
 /*
 / this function is for returning the monthly
-/ stats for a website that's in the database
+/ stats for a website that's in the database,
+/ maintenance is left out like in the daily stats
  */
 async fn get_monthly_stats(alias: &str, db: &PgPool) -> Result<Vec<WebsiteStats>, ApiError> {
     let data = sqlx::query_as::<_, WebsiteStats>(
         r#"
         SELECT date_trunc('day', created_at) AS time,
-        CAST(COUNT(CASE WHEN status=200 THEN 1 END) * 100 / COUNT(*) AS int2) AS uptime_pct
+        CAST(COUNT(*) FILTER (WHERE is_up AND NOT in_maintenance) * 100
+            / NULLIF(COUNT(*) FILTER (WHERE NOT in_maintenance), 0) AS int2) AS uptime_pct,
+        CAST(AVG(response_time_ms) FILTER (WHERE NOT in_maintenance) AS int4) AS avg_response_ms,
+        CAST(percentile_cont(0.95) WITHIN GROUP (ORDER BY response_time_ms)
+            FILTER (WHERE NOT in_maintenance) AS int4) AS p95_response_ms,
+        CAST(percentile_cont(0.99) WITHIN GROUP (ORDER BY response_time_ms)
+            FILTER (WHERE NOT in_maintenance) AS int4) AS p99_response_ms,
+        CAST(COUNT(*) FILTER (WHERE in_maintenance) * 100 / COUNT(*) AS int2) AS maintenance_pct
         FROM logs
         LEFT JOIN websites ON websites.id=logs.website_id
         WHERE websites.alias=$1
@@ -228,6 +716,7 @@ async fn get_monthly_stats(alias: &str, db: &PgPool) -> Result<Vec<WebsiteStats>
     let no_of_splits = 30;
     let no_of_seconds = 86400;
     let data = fill_data_gaps(data, no_of_splits, SplitBy::Day, no_of_seconds);
+    let data = mark_paused(data, &get_pauses(alias, db).await?, no_of_seconds);
 
     Ok(data)
 }
@@ -263,6 +752,11 @@ fn fill_data_gaps(mut data: Vec<WebsiteStats>, splits: i32, format: SplitBy, no_
                 data.push(WebsiteStats {
                     time,
                     uptime_pct: None,
+                    avg_response_ms: None,
+                    p95_response_ms: None,
+                    p99_response_ms: None,
+                    maintenance_pct: None,
+                    paused: false,
                 });
             }
         }
@@ -274,13 +768,55 @@ fn fill_data_gaps(mut data: Vec<WebsiteStats>, splits: i32, format: SplitBy, no_
     data
 }
 
+/*
+/ a period in which a website wasn't checked because it
+/ was paused, it's still going on if it hasn't been resumed
+ */
+#[derive(sqlx::FromRow)]
+struct Pause {
+    paused_at: DateTime<Utc>,
+    resumed_at: Option<DateTime<Utc>>,
+}
+
+async fn get_pauses(alias: &str, db: &PgPool) -> Result<Vec<Pause>, ApiError> {
+    let pauses = sqlx::query_as::<_, Pause>(
+        "SELECT website_pauses.paused_at, website_pauses.resumed_at FROM website_pauses
+        JOIN websites ON websites.id = website_pauses.website_id
+        WHERE websites.alias = $1
+        AND coalesce(website_pauses.resumed_at, current_timestamp) > current_timestamp - interval '31 days'",
+    )
+    .bind(alias)
+    .fetch_all(db)
+    .await?;
+
+    Ok(pauses)
+}
+
+/*
+/ splits without any checks that overlap a pause were paused
+/ rather than missing data
+ */
+fn mark_paused(mut data: Vec<WebsiteStats>, pauses: &[Pause], no_of_seconds: i32) -> Vec<WebsiteStats> {
+    let now = Utc::now();
+
+    for split in data.iter_mut().filter(|split| split.uptime_pct.is_none() && split.maintenance_pct.is_none()) {
+        let split_end = split.time + chrono::Duration::seconds(no_of_seconds.into());
+
+        split.paused = pauses
+            .iter()
+            .any(|pause| pause.paused_at < split_end && pause.resumed_at.unwrap_or(now) > split.time);
+    }
+
+    data
+}
+
 /*
 / this function returns a log of website data
 / for the website that matches the given alias
  */
 async fn get_website_by_alias(State(state): State<AppState>, Path(alias): Path<String>)
     -> Result<impl AskamaIntoResponse, ApiError> {
-    let website = sqlx::query_as::<_, Website>("SELECT url, alias FROM websites WHERE alias = $1")
+    let website = sqlx::query_as::<_, Website>("SELECT * FROM websites WHERE alias = $1")
         .bind(&alias)
         .fetch_one(&state.db)
         .await?;
@@ -288,51 +824,730 @@ async fn get_website_by_alias(State(state): State<AppState>, Path(alias): Path<S
     let last_24_hours_data = get_daily_stats(&website.alias, &state.db).await?;
     let monthly_data = get_monthly_stats(&website.alias, &state.db).await?;
 
-    let incidents = sqlx::query_as::<_, Incident>(
-        "SELECT logs.created_at AS time,
-        logs.status FROM logs
-        LEFT JOIN websites ON websites.id=logs.website_id
-        WHERE websites.alias=$1 AND logs.status!=200",
+    let incidents = Incident::for_website(&state.db, website.id).await?;
+    let acknowledge_signatures = incidents
+        .iter()
+        .filter(|incident| incident.resolved_at.is_none() && incident.acknowledged_at.is_none())
+        .filter_map(|incident| {
+            let cipher = state.cipher.as_ref()?;
+            Some((incident.id, cipher.sign(&Incident::acknowledge_message(incident.id))))
+        })
+        .collect();
+
+    let certificate = Certificate::for_website(&state.db, website.id).await?;
+
+    let job_runs = sqlx::query_as::<_, JobRun>(
+        "SELECT received_at, duration_ms FROM heartbeats
+        WHERE website_id=$1 AND kind='finish'
+        ORDER BY received_at DESC
+        LIMIT 10",
     )
-    .bind(&alias)
+    .bind(website.id)
     .fetch_all(&state.db)
     .await?;
 
+    let channels = LinkedChannel::for_website(&state.db, website.id).await?;
+    let available_channels = NotificationChannel::all(&state.db)
+        .await?
+        .into_iter()
+        .filter(|channel| !channels.iter().any(|linked| linked.channel.id == channel.id))
+        .collect();
+
+    let maintenance_windows = MaintenanceWindow::for_website(&state.db, website.id).await?;
+    let changes = WebsiteChange::for_website(&state.db, website.id).await?;
+
     let log = WebsiteInfo {
-        url: website.url,
+        url: website.url.clone(),
         alias,
+        flapping_since: website.flapping_since,
+        paused_at: website.paused_at,
         data: last_24_hours_data,
     };
 
     Ok(SingleWebsiteLogs {
         log,
+        website,
+        certificate,
         incidents,
+        job_runs,
+        channels,
+        available_channels,
+        maintenance_windows,
+        changes,
         monthly_data,
+        acknowledge_signatures,
     })
 }
 
-async fn delete_website(State(state): State<AppState>, Path(alias): Path<String>)
+/*
+/ the form used to add a notification channel. the secret
+/ signs webhook payloads or is the routing or api key of a
+/ paging service, it's encrypted before it's stored like
+/ monitor credentials
+ */
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "validate_channel_target"))]
+struct ChannelForm {
+    #[validate(length(min = 1, max = 75))]
+    name: String,
+    #[serde(default = "default_channel_kind")]
+    kind: ChannelKind,
+    url: String,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    secret: Option<String>,
+}
+
+fn default_channel_kind() -> ChannelKind {
+    ChannelKind::Webhook
+}
+
+fn validate_channel_target(form: &ChannelForm) -> Result<(), ValidationError> {
+    let valid = match form.kind {
+        ChannelKind::Webhook => {
+            form.url.validate_url() && (form.url.starts_with("http://") || form.url.starts_with("https://"))
+        }
+        ChannelKind::Email => email_recipients(&form.url).is_ok(),
+        ChannelKind::Slack | ChannelKind::Discord | ChannelKind::Teams => {
+            form.url.validate_url() && form.url.starts_with("https://")
+        }
+        // paging services can't be reached without their routing or api key
+        ChannelKind::PagerDuty | ChannelKind::Opsgenie => {
+            form.url.validate_url() && form.url.starts_with("https://") && form.secret.is_some()
+        }
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("url"))
+    }
+}
+
+#[derive(Deserialize)]
+struct WebsiteChannelForm {
+    channel_id: i32,
+    #[serde(default)]
+    escalate_after_mins: u16,
+}
+
+async fn get_channels(State(state): State<AppState>) -> Result<impl AskamaIntoResponse, ApiError> {
+    let channels = NotificationChannel::all(&state.db).await?;
+    let deliveries = Delivery::recent(&state.db).await?;
+
+    Ok(ChannelsPage { channels, deliveries })
+}
+
+async fn create_channel(State(state): State<AppState>, Form(new_channel): Form<ChannelForm>)
+    -> Result<impl AxumIntoResponse, ApiError> {
+    if let Err(e) = new_channel.validate() {
+        return Err(ApiError::Validation(format!(
+            "does your channel have a name and a valid URL for its kind? {e}"
+        )));
+    }
+
+    let secret = match (&state.cipher, new_channel.secret.as_deref()) {
+        (Some(cipher), Some(secret)) => Some(cipher.encrypt(secret)),
+        (_, None) => None,
+        (None, Some(_)) => {
+            return Err(ApiError::Validation(
+                "MONITOR_SECRETS_KEY must be configured to store channel secrets".to_owned(),
+            ));
+        }
+    };
+
+    sqlx::query("INSERT INTO notification_channels (name, kind, url, secret) VALUES ($1, $2, $3, $4)")
+        .bind(new_channel.name)
+        .bind(new_channel.kind)
+        .bind(new_channel.url)
+        .bind(secret)
+        .execute(&state.db)
+        .await
+        .map_err(|e| match e.as_database_error().and_then(|e| e.code()) {
+            Some(code) if code == "23505" => ApiError::Conflict("a channel with this name already exists".to_owned()),
+            _ => ApiError::from(e),
+        })?;
+
+    Ok(Redirect::to("/channels"))
+}
+
+async fn delete_channel(State(state): State<AppState>, Path(id): Path<i32>)
+    -> Result<impl AxumIntoResponse, ApiError> {
+    sqlx::query("DELETE FROM notification_channels WHERE id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(StatusCode::OK)
+}
+
+/*
+/ send a test notification to a channel, the result is
+/ swapped into channels.html next to the test button
+ */
+async fn test_channel(State(state): State<AppState>, Path(id): Path<i32>)
+    -> Result<impl AxumIntoResponse, ApiError> {
+    let channel = sqlx::query_as::<_, NotificationChannel>("SELECT * FROM notification_channels WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let result = match state.notifier.send_test(&channel).await {
+        Ok(()) => "✅ Test notification sent".to_owned(),
+        Err(e) => format!("❌ {}", e.message),
+    };
+
+    Ok(result)
+}
+
+/*
+/ link a notification channel to a monitor so it gets told
+/ when the monitor goes down or comes back up. linking it
+/ again changes how long it waits before being escalated to
+ */
+async fn add_website_channel(
+    State(state): State<AppState>,
+    Path(alias): Path<String>,
+    Form(form): Form<WebsiteChannelForm>,
+) -> Result<impl AxumIntoResponse, ApiError> {
+    sqlx::query(
+        "INSERT INTO website_channels (website_id, channel_id, escalate_after_mins) \
+        SELECT id, $2, $3 FROM websites WHERE alias = $1 \
+        ON CONFLICT (website_id, channel_id) DO UPDATE SET escalate_after_mins = EXCLUDED.escalate_after_mins"
+    )
+        .bind(&alias)
+        .bind(form.channel_id)
+        .bind(form.escalate_after_mins as i32)
+        .execute(&state.db)
+        .await?;
+
+    Ok(Redirect::to(&format!("/websites/{alias}")))
+}
+
+async fn remove_website_channel(State(state): State<AppState>, Path((alias, channel_id)): Path<(String, i32)>)
+    -> Result<impl AxumIntoResponse, ApiError> {
+    sqlx::query(
+        "DELETE FROM website_channels USING websites \
+        WHERE websites.id = website_channels.website_id AND websites.alias = $1 AND website_channels.channel_id = $2"
+    )
+        .bind(&alias)
+        .bind(channel_id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(StatusCode::OK)
+}
+
+/*
+/ the form used to add a maintenance window. times come from
+/ datetime-local inputs and are taken as UTC. monitors is a
+/ comma separated list of aliases, leaving it empty makes a
+/ window that applies to all monitors
+ */
+#[derive(Deserialize, Validate)]
+#[validate(schema(function = "validate_maintenance_schedule"))]
+struct MaintenanceForm {
+    #[validate(length(min = 1, max = 75))]
+    name: String,
+    #[serde(default)]
+    monitors: String,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    starts_at: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    ends_at: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    cron: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    duration_mins: Option<i32>,
+}
+
+impl MaintenanceForm {
+    /*
+    / sorted and without duplicates, so every alias
+    / should match exactly one monitor
+     */
+    fn aliases(&self) -> Vec<String> {
+        let mut aliases: Vec<String> = self.monitors
+            .split(',')
+            .map(str::trim)
+            .filter(|alias| !alias.is_empty())
+            .map(str::to_owned)
+            .collect();
+        aliases.sort();
+        aliases.dedup();
+        aliases
+    }
+}
+
+fn parse_form_time(value: Option<&str>) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value?, "%Y-%m-%dT%H:%M")
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+/*
+/ a window is either one-off with a start and an end, or
+/ recurring with a cron expression and a duration. the
+/ error says which, and why a cron expression didn't parse
+ */
+fn validate_maintenance_schedule(form: &MaintenanceForm) -> Result<(), ValidationError> {
+    let parse_time = |value: Option<&String>| match value {
+        Some(value) => parse_form_time(Some(value)).map(Some).ok_or_else(|| format!("{value:?} is not a valid time")),
+        None => Ok(None),
+    };
+
+    let problem = match (&form.cron, form.duration_mins) {
+        (None, None) => match (parse_time(form.starts_at.as_ref()), parse_time(form.ends_at.as_ref())) {
+            (Err(e), _) | (_, Err(e)) => Some(e),
+            (Ok(Some(starts_at)), Ok(Some(ends_at))) if starts_at < ends_at => None,
+            (Ok(Some(_)), Ok(Some(_))) => Some("a window has to end after it starts".to_owned()),
+            _ => Some("a window needs either a start and an end, or a cron expression and a duration".to_owned()),
+        },
+        (Some(cron), Some(duration_mins)) => {
+            if form.starts_at.is_some() || form.ends_at.is_some() {
+                Some("a recurring window can't also have a start or an end".to_owned())
+            } else if let Err(e) = parse_cron(cron) {
+                Some(e)
+            } else if !(1..=MAX_DURATION_MINS).contains(&duration_mins) {
+                Some(format!("a window has to last between 1 and {MAX_DURATION_MINS} minutes"))
+            } else {
+                None
+            }
+        }
+        _ => Some("a recurring window needs both a cron expression and a duration".to_owned()),
+    };
+
+    match problem {
+        Some(message) => Err(ValidationError::new("schedule").with_message(message.into())),
+        None => Ok(()),
+    }
+}
+
+async fn get_maintenance_windows(State(state): State<AppState>) -> Result<impl AskamaIntoResponse, ApiError> {
+    let windows = MaintenanceWindow::all(&state.db).await?;
+
+    Ok(MaintenancePage { windows })
+}
+
+async fn create_maintenance_window(State(state): State<AppState>, Form(new_window): Form<MaintenanceForm>)
+    -> Result<impl AxumIntoResponse, ApiError> {
+    if let Err(e) = new_window.validate() {
+        return Err(ApiError::Validation(format!("does your window have a name and a valid schedule? {e}")));
+    }
+
+    let aliases = new_window.aliases();
+    let website_ids: Vec<i32> = sqlx::query_scalar("SELECT id FROM websites WHERE alias = ANY($1)")
+        .bind(&aliases)
+        .fetch_all(&state.db)
+        .await?;
+
+    if website_ids.len() != aliases.len() {
+        return Err(ApiError::Validation(format!("{} aren't all monitors", aliases.join(", "))));
+    }
+
+    // the window and its monitors are saved together, a window
+    // without its links would apply to nothing
+    let mut tx = state.db.begin().await?;
+
+    let window_id: i32 = sqlx::query_scalar(
+        "INSERT INTO maintenance_windows (name, starts_at, ends_at, cron, duration_mins, applies_to_all) \
+        VALUES ($1, $2, $3, $4, $5, $6) RETURNING id"
+    )
+        .bind(&new_window.name)
+        .bind(parse_form_time(new_window.starts_at.as_deref()))
+        .bind(parse_form_time(new_window.ends_at.as_deref()))
+        .bind(new_window.cron.as_deref().map(str::trim))
+        .bind(new_window.duration_mins)
+        .bind(aliases.is_empty())
+        .fetch_one(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO maintenance_window_websites (window_id, website_id) SELECT $1, unnest($2::int4[])"
+    )
+        .bind(window_id)
+        .bind(&website_ids)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Redirect::to("/maintenance"))
+}
+
+async fn delete_maintenance_window(State(state): State<AppState>, Path(id): Path<i32>)
+    -> Result<impl AxumIntoResponse, ApiError> {
+    sqlx::query("DELETE FROM maintenance_windows WHERE id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(StatusCode::OK)
+}
+
+async fn edit_website(State(state): State<AppState>, Path(alias): Path<String>)
     -> Result<impl AskamaIntoResponse, ApiError> {
+    let website = sqlx::query_as::<_, Website>("SELECT * FROM websites WHERE alias = $1")
+        .bind(&alias)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(EditWebsitePage {
+        website,
+        http_methods: &HTTP_METHODS,
+        dns_record_types: &DNS_RECORD_TYPES,
+    })
+}
+
+/*
+/ change the url, alias or any other setting of a monitor.
+/ its logs and incidents are linked by id so they stay with
+/ it, and every setting that changed is kept in its history.
+/ PUT takes the whole form, the edit page fills it in with
+/ the current settings. headers and the auth secret
+/ are kept when they're left empty since they can't be shown,
+/ headers are only removed with clear_request_headers
+ */
+async fn update_website(
+    State(state): State<AppState>,
+    Path(alias): Path<String>,
+    Form(form): Form<WebsiteForm>,
+) -> Result<impl AxumIntoResponse, ApiError> {
+    let updated = save_website(&state, &alias, form).await?;
+
+    // the edit form is sent by htmx, which follows HX-Redirect
+    let location = format!("/websites/{}", updated.alias);
+    Ok((StatusCode::OK, [("HX-Redirect", location)], "Updated"))
+}
+
+async fn save_website(state: &AppState, alias: &str, mut form: WebsiteForm) -> Result<Website, ApiError> {
+    // the monitor is locked until its update and history are saved together
     let mut tx = state.db.begin().await?;
 
-    if let Err(e) = sqlx::query("DELETE FROM logs WHERE website_alias=$1")
+    let website = sqlx::query_as::<_, Website>("SELECT * FROM websites WHERE alias = $1 FOR UPDATE")
+        .bind(alias)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let stored_headers = stored_secret(&state.cipher, &website.request_headers);
+    let stored_auth_secret = stored_secret(&state.cipher, &website.auth_secret);
+
+    if form.request_headers.is_none() && !form.clear_request_headers {
+        form.request_headers = keep_secret(&state.cipher, &website.request_headers, "headers")?;
+    }
+    if form.auth_secret.is_none() && form.auth_kind == website.auth_kind {
+        form.auth_secret = keep_secret(&state.cipher, &website.auth_secret, "auth secret")?;
+    }
+
+    if let Err(e) = form.validate() {
+        return Err(ApiError::Validation(format!(
+            "is your website a reachable URL with sensible check settings? {e}"
+        )));
+    }
+
+    let (request_headers, auth_secret) = encrypt_secrets(state, &form)?;
+
+    // a stored value that can't be decrypted counts as changed when it's replaced or cleared
+    let changed = |encrypted: &Option<Vec<u8>>, stored: &Option<String>, new: &Option<String>| {
+        stored != new || encrypted.is_some() != new.is_some()
+    };
+    let changed_secrets: Vec<&str> = [
+        ("request_headers", changed(&website.request_headers, &stored_headers, &form.request_headers)),
+        ("auth_secret", changed(&website.auth_secret, &stored_auth_secret, &form.auth_secret)),
+    ]
+        .into_iter()
+        .filter_map(|(secret, changed)| changed.then_some(secret))
+        .collect();
+
+    // a heartbeat monitor keeps the url its job pings, one that becomes a heartbeat monitor gets a new one
+    let (url, heartbeat_token) = match (form.kind, website.kind, &website.heartbeat_token) {
+        (MonitorKind::Heartbeat, MonitorKind::Heartbeat, Some(token)) => (website.url.clone(), Some(token.clone())),
+        (MonitorKind::Heartbeat, _, _) => {
+            let token = heartbeat_token();
+            (format!("/heartbeat/{token}"), Some(token))
+        }
+        _ => (form.url, None),
+    };
+
+    let updated = sqlx::query_as::<_, Website>(
+        "UPDATE websites SET \
+        kind = $2, url = $3, alias = $4, check_interval_secs = $5, timeout_secs = $6, retries = $7, \
+        confirm_failures = $8, accepted_statuses = $9, body_contains = $10, body_not_contains = $11, \
+        body_regex = $12, min_body_bytes = $13, max_body_bytes = $14, json_assertions = $15, http_method = $16, \
+        request_headers = $17, request_body = $18, auth_kind = $19, auth_username = $20, auth_secret = $21, \
+        cert_expiry_days = $22, tcp_send = $23, tcp_expect = $24, dns_record_type = $25, dns_resolver = $26, \
+        dns_expected = $27, heartbeat_token = $28, heartbeat_grace_secs = $29 \
+        WHERE id = $1 RETURNING *"
+    )
+        .bind(website.id)
+        .bind(form.kind)
+        .bind(url)
+        .bind(form.alias)
+        .bind(form.check_interval_secs)
+        .bind(form.timeout_secs)
+        .bind(form.retries)
+        .bind(form.confirm_failures)
+        .bind(form.accepted_statuses)
+        .bind(form.body_contains)
+        .bind(form.body_not_contains)
+        .bind(form.body_regex)
+        .bind(form.min_body_bytes)
+        .bind(form.max_body_bytes)
+        .bind(form.json_assertions)
+        .bind(form.http_method)
+        .bind(request_headers)
+        .bind(form.request_body)
+        .bind(form.auth_kind)
+        .bind(form.auth_username)
+        .bind(auth_secret)
+        .bind(form.cert_expiry_days)
+        .bind(form.tcp_send)
+        .bind(form.tcp_expect)
+        .bind(form.dns_record_type)
+        .bind(form.dns_resolver)
+        .bind(form.dns_expected)
+        .bind(heartbeat_token)
+        .bind(form.heartbeat_grace_secs)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(alias_conflict)?;
+
+    WebsiteChange::record(&mut tx, &website, &updated, &changed_secrets).await?;
+    tx.commit().await?;
+
+    Ok(updated)
+}
+
+/*
+/ the plain text of a header or secret that's stored encrypted
+ */
+fn stored_secret(cipher: &Option<SecretCipher>, encrypted: &Option<Vec<u8>>) -> Option<String> {
+    cipher.as_ref()?.decrypt(encrypted.as_deref()?).ok()
+}
+
+/*
+/ keeping a stored header or secret means storing it again,
+/ so when it can't be decrypted (no or another secrets key)
+/ the edit is refused rather than quietly dropping it
+ */
+fn keep_secret(cipher: &Option<SecretCipher>, encrypted: &Option<Vec<u8>>, name: &str)
+    -> Result<Option<String>, ApiError> {
+    let Some(encrypted) = encrypted else {
+        return Ok(None);
+    };
+
+    cipher
+        .as_ref()
+        .and_then(|cipher| cipher.decrypt(encrypted).ok())
+        .map(Some)
+        .ok_or_else(|| ApiError::Validation(format!(
+            "the stored {name} can't be decrypted with MONITOR_SECRETS_KEY, enter them again or clear them"
+        )))
+}
+
+/*
+/ a paused monitor isn't checked until it's resumed, unlike
+/ deleting it this keeps its history. pausing or resuming it
+/ twice does nothing
+ */
+async fn pause_website(State(state): State<AppState>, Path(alias): Path<String>)
+    -> Result<impl AxumIntoResponse, ApiError> {
+    sqlx::query(
+        "WITH paused AS ( \
+            UPDATE websites SET paused_at = current_timestamp \
+            WHERE alias = $1 AND paused_at IS NULL RETURNING id, paused_at \
+        ) \
+        INSERT INTO website_pauses (website_id, paused_at) SELECT id, paused_at FROM paused"
+    )
+        .bind(&alias)
+        .execute(&state.db)
+        .await?;
+
+    Ok(Redirect::to(&format!("/websites/{alias}")))
+}
+
+async fn resume_website(State(state): State<AppState>, Path(alias): Path<String>)
+    -> Result<impl AxumIntoResponse, ApiError> {
+    sqlx::query(
+        "WITH resumed AS ( \
+            UPDATE websites SET paused_at = NULL WHERE alias = $1 AND paused_at IS NOT NULL RETURNING id \
+        ) \
+        UPDATE website_pauses SET resumed_at = current_timestamp FROM resumed \
+        WHERE website_pauses.website_id = resumed.id AND website_pauses.resumed_at IS NULL"
+    )
         .bind(&alias)
+        .execute(&state.db)
+        .await?;
+
+    Ok(Redirect::to(&format!("/websites/{alias}")))
+}
+
+#[derive(Deserialize)]
+struct AcknowledgeLink {
+    signature: String,
+}
+
+/*
+/ acknowledging an incident stops it from being escalated to
+/ any more channels. both the button on single_website.html
+/ and the confirmation page of the signed links in notifications
+/ post the incident's signature and are sent back to the
+/ monitor's page
+ */
+async fn acknowledge_incident(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Form(link): Form<AcknowledgeLink>,
+) -> Result<impl AxumIntoResponse, ApiError> {
+    let (alias, _) = signed_incident(&state, id, &link).await?;
+    Incident::acknowledge(&state.db, id).await?;
+
+    Ok(Redirect::to(&format!("/websites/{alias}")))
+}
+
+/*
+/ the signed link in notifications only shows what's being
+/ acknowledged, so mail scanners and link previews that
+/ follow it don't acknowledge the incident for anyone
+ */
+async fn acknowledge_incident_link(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Query(link): Query<AcknowledgeLink>,
+) -> Result<impl AskamaIntoResponse, ApiError> {
+    let (alias, incident) = signed_incident(&state, id, &link).await?;
+
+    Ok(AcknowledgePage {
+        alias,
+        incident,
+        signature: link.signature,
+    })
+}
+
+async fn signed_incident(state: &AppState, id: i32, link: &AcknowledgeLink) -> Result<(String, Incident), ApiError> {
+    let signed = state
+        .cipher
+        .as_ref()
+        .is_some_and(|cipher| cipher.verify(&Incident::acknowledge_message(id), &link.signature));
+
+    if !signed {
+        return Err(ApiError::Forbidden);
+    }
+
+    let incident = Incident::by_id(&state.db, id).await?.ok_or(ApiError::NotFound)?;
+
+    let alias: String = sqlx::query_scalar("SELECT alias FROM websites WHERE id = $1")
+        .bind(incident.website_id)
+        .fetch_one(&state.db)
+        .await?;
+
+    Ok((alias, incident))
+}
+
+fn heartbeat_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+/*
+/ jobs watched by a heartbeat monitor ping its secret url
+/ when they run. a plain ping just says the job is alive,
+/ start and finish pings around a run also record how long
+/ the run took
+ */
+async fn receive_heartbeat(State(state): State<AppState>, Path(token): Path<String>)
+    -> Result<impl AxumIntoResponse, ApiError> {
+    record_heartbeat(&state.db, &token, "ping").await
+}
+
+async fn receive_heartbeat_event(State(state): State<AppState>, Path((token, event)): Path<(String, String)>)
+    -> Result<impl AxumIntoResponse, ApiError> {
+    match event.as_str() {
+        "ping" | "start" | "finish" => record_heartbeat(&state.db, &token, &event).await,
+        _ => Err(ApiError::NotFound),
+    }
+}
+
+async fn record_heartbeat(db: &PgPool, token: &str, event: &str) -> Result<impl AxumIntoResponse, ApiError> {
+    let website_id: i32 = sqlx::query_scalar("SELECT id FROM websites WHERE heartbeat_token = $1 AND kind = 'heartbeat'")
+        .bind(token)
+        .fetch_optional(db)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    // a finish belongs to the latest start that hasn't been finished yet
+    let duration_ms = if event == "finish" {
+        let started_at: Option<DateTime<Utc>> = sqlx::query_scalar(
+            "SELECT max(received_at) FROM heartbeats
+            WHERE website_id = $1 AND kind = 'start' AND received_at > coalesce(
+                (SELECT max(received_at) FROM heartbeats WHERE website_id = $1 AND kind = 'finish'),
+                '-infinity'
+            )"
+        )
+            .bind(website_id)
+            .fetch_one(db)
+            .await?;
+
+        started_at.map(|started_at| (Utc::now() - started_at).num_milliseconds().try_into().unwrap_or(i32::MAX))
+    } else {
+        None
+    };
+
+    sqlx::query("INSERT INTO heartbeats (website_id, kind, duration_ms) VALUES ($1, $2, $3)")
+        .bind(website_id)
+        .bind(event)
+        .bind(duration_ms)
+        .execute(db)
+        .await?;
+
+    Ok(StatusCode::OK)
+}
+
+async fn delete_website(State(state): State<AppState>, Path(alias): Path<String>)
+    -> Result<impl AskamaIntoResponse, ApiError> {
+    remove_website(&state.db, &alias).await?;
+
+    Ok(StatusCode::OK)
+}
+
+/*
+/ logs are the only thing not deleted along with a website
+/ by the database, everything else cascades
+ */
+async fn remove_website(db: &PgPool, alias: &str) -> Result<(), ApiError> {
+    let mut tx = db.begin().await?;
+
+    if let Err(e) = sqlx::query("DELETE FROM logs WHERE website_id = (SELECT id FROM websites WHERE alias=$1)")
+        .bind(alias)
         .execute(&mut *tx)
         .await {
         tx.rollback().await?;
         return Err(ApiError::SQLError(e));
     };
 
-    if let Err(e) = sqlx::query("DELETE FROM websites WHERE alias=$1")
-        .bind(&alias)
+    let deleted = match sqlx::query("DELETE FROM websites WHERE alias=$1")
+        .bind(alias)
         .execute(&mut *tx)
         .await {
+        Ok(deleted) => deleted,
+        Err(e) => {
+            tx.rollback().await?;
+            return Err(ApiError::SQLError(e));
+        }
+    };
+
+    if deleted.rows_affected() == 0 {
         tx.rollback().await?;
-        return Err(ApiError::SQLError(e));
+        return Err(ApiError::NotFound);
     }
 
     tx.commit().await?;
-    Ok(StatusCode::OK)
+    Ok(())
 }
 
 // assuming our style css lives at "templates/styles.css"
@@ -351,31 +1566,82 @@ async fn hello_world() {
 #[derive(Clone)]
 struct AppState {
     db: PgPool,
+    cipher: Option<SecretCipher>,
+    notifier: Notifier,
 }
 
 impl AppState {
-    fn new(db: PgPool) -> Self {
-        Self {db}
+    fn new(db: PgPool, cipher: Option<SecretCipher>, notifier: Notifier) -> Self {
+        Self {db, cipher, notifier}
     }
 }
 
 #[shuttle_runtime::main]
-async fn main(#[shuttle_shared_db::Postgres] db: PgPool)
-    -> shuttle_axum::ShuttleAxum {
+async fn main(
+    #[shuttle_shared_db::Postgres] db: PgPool,
+    #[shuttle_runtime::Secrets] secrets: SecretStore,
+) -> shuttle_axum::ShuttleAxum {
     sqlx::migrate!().run(&db).await.unwrap();
 
-    let state = AppState::new(db.clone());
+    let cipher = SecretCipher::from_secrets(&secrets);
+    let notifier = Notifier::new(
+        db.clone(),
+        cipher.clone(),
+        Mailer::from_secrets(&secrets),
+        secrets.get("PUBLIC_URL"),
+    );
+    notifier.resume_deliveries().await;
+    let state = AppState::new(db.clone(), cipher.clone(), notifier.clone());
+
+    let scheduler = Scheduler::new(db, cipher, notifier, SchedulerConfig::from_secrets(&secrets));
 
     tokio::spawn(async move {
-        check_websites(db).await;
+        scheduler.run().await;
     });
 
     let router = Router::new()
         .route("/", get(get_websites))
         .route("/websites", post(create_website))
-        .route("/websites/:alias", get(get_website_by_alias).delete(delete_website))
+        .route(
+            "/websites/:alias",
+            get(get_website_by_alias).put(update_website).delete(delete_website),
+        )
+        .route("/websites/:alias/edit", get(edit_website))
+        .route("/websites/:alias/pause", post(pause_website))
+        .route("/websites/:alias/resume", post(resume_website))
+        .route("/websites/:alias/channels", post(add_website_channel))
+        .route("/websites/:alias/channels/:channel_id", delete(remove_website_channel))
+        .route("/channels", get(get_channels).post(create_channel))
+        .route("/channels/:id", delete(delete_channel))
+        .route("/channels/:id/test", post(test_channel))
+        .route("/maintenance", get(get_maintenance_windows).post(create_maintenance_window))
+        .route("/maintenance/:id", delete(delete_maintenance_window))
+        .route("/incidents/:id/acknowledge", get(acknowledge_incident_link).post(acknowledge_incident))
+        .route("/heartbeat/:token", post(receive_heartbeat))
+        .route("/heartbeat/:token/:event", post(receive_heartbeat_event))
         .route("/styles.css", get(styles))
+        .nest("/api/v1", api::router())
+        .merge(SwaggerUi::new("/api/docs").url("/api/v1/openapi.json", ApiDoc::openapi()))
         .with_state(state);
 
     Ok(router.into())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maintenance_aliases_are_deduplicated() {
+        let form = MaintenanceForm {
+            name: "deploy".to_owned(),
+            monitors: "web, api,, api ,web".to_owned(),
+            starts_at: None,
+            ends_at: None,
+            cron: None,
+            duration_mins: None,
+        };
+
+        assert_eq!(form.aliases(), ["api", "web"]);
+    }
+}
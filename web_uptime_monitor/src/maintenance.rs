@@ -0,0 +1,185 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, Utc};
+use cron::Schedule;
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::Website;
+
+/*
+/ the longest a recurring window can last, a week
+ */
+pub const MAX_DURATION_MINS: i32 = 7 * 24 * 60;
+
+/*
+/ a period in which monitors are still checked, but their
+/ checks are tagged so they don't count against uptime and
+/ nobody is notified about them. a window is either one-off,
+/ from starts_at until ends_at, or recurring, starting on
+/ every occurrence of a cron expression and lasting for
+/ duration_mins. a window either applies to all monitors or
+/ only to the ones it was given, and once those are all
+/ deleted it covers none
+ */
+#[derive(sqlx::FromRow, Serialize)]
+pub struct MaintenanceWindow {
+    pub id: i32,
+    pub name: String,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub cron: Option<String>,
+    pub duration_mins: Option<i32>,
+    pub applies_to_all: bool,
+    pub created_at: DateTime<Utc>,
+    pub aliases: Vec<String>,
+}
+
+const SELECT_WINDOWS: &str = "SELECT maintenance_windows.*, \
+    coalesce(array_agg(websites.alias ORDER BY websites.alias) FILTER (WHERE websites.id IS NOT NULL), '{}')::text[] \
+    AS aliases \
+    FROM maintenance_windows \
+    LEFT JOIN maintenance_window_websites ON maintenance_window_websites.window_id = maintenance_windows.id \
+    LEFT JOIN websites ON websites.id = maintenance_window_websites.website_id \
+    GROUP BY maintenance_windows.id";
+
+impl MaintenanceWindow {
+    pub async fn all(db: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(&format!("{SELECT_WINDOWS} ORDER BY maintenance_windows.created_at DESC"))
+            .fetch_all(db)
+            .await
+    }
+
+    /*
+    / the windows that cover a website, either because it was
+    / picked or because the window applies to all monitors
+     */
+    pub async fn for_website(db: &PgPool, website_id: i32) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(&format!(
+            "{SELECT_WINDOWS} HAVING maintenance_windows.applies_to_all OR bool_or(websites.id = $1) \
+            ORDER BY maintenance_windows.created_at DESC"
+        ))
+            .bind(website_id)
+            .fetch_all(db)
+            .await
+    }
+
+    /*
+    / whether a check of the website run now falls in one of
+    / its maintenance windows
+     */
+    pub async fn covers(db: &PgPool, website: &Website, at: DateTime<Utc>) -> Result<bool, sqlx::Error> {
+        let windows = Self::for_website(db, website.id).await?;
+
+        Ok(windows.iter().any(|window| window.is_active_at(at)))
+    }
+
+    pub fn is_active_at(&self, at: DateTime<Utc>) -> bool {
+        self.period_around(at).is_some_and(|(starts_at, ends_at)| starts_at <= at && at < ends_at)
+    }
+
+    /*
+    / the period that's going on at the given time, or else the
+    / next one. one-off windows that are over have none left
+     */
+    pub fn period_around(&self, at: DateTime<Utc>) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        if let (Some(starts_at), Some(ends_at)) = (self.starts_at, self.ends_at) {
+            return (at < ends_at).then_some((starts_at, ends_at));
+        }
+
+        let schedule = parse_cron(self.cron.as_deref()?).ok()?;
+        let duration = Duration::minutes(self.duration_mins?.into());
+
+        // the latest occurrence that started by now, one second is added so one starting right now counts
+        let current = schedule
+            .after(&(at + Duration::seconds(1)))
+            .next_back()
+            .filter(|starts_at| at < *starts_at + duration);
+
+        current
+            .or_else(|| schedule.after(&at).next())
+            .map(|starts_at| (starts_at, starts_at + duration))
+    }
+
+    pub fn describe(&self) -> String {
+        match (self.starts_at, self.ends_at, &self.cron, self.duration_mins) {
+            (Some(starts_at), Some(ends_at), _, _) => format!("From {starts_at} until {ends_at}"),
+            (_, _, Some(cron), Some(duration_mins)) => format!("On {cron} for {duration_mins} minutes"),
+            _ => "Never".to_owned(),
+        }
+    }
+
+    pub fn status(&self) -> String {
+        let now = Utc::now();
+
+        match self.period_around(now) {
+            Some((starts_at, ends_at)) if starts_at <= now => format!("🔧 In progress until {ends_at}"),
+            Some((starts_at, _)) => format!("Next one starts {starts_at}"),
+            None => "Over".to_owned(),
+        }
+    }
+}
+
+/*
+/ cron expressions include seconds, e.g. "0 0 2 * * Sun" is
+/ every sunday at 02:00 UTC. an optional seventh field
+/ restricts the years
+ */
+pub fn parse_cron(expression: &str) -> Result<Schedule, String> {
+    Schedule::from_str(expression.trim()).map_err(|e| format!("{expression:?} is not a valid cron expression: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(day: u32, hour: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, day, hour, min, 0).unwrap()
+    }
+
+    fn window(starts_at: Option<DateTime<Utc>>, ends_at: Option<DateTime<Utc>>, cron: Option<&str>) -> MaintenanceWindow {
+        MaintenanceWindow {
+            id: 1,
+            name: "deploy".to_owned(),
+            starts_at,
+            ends_at,
+            cron: cron.map(str::to_owned),
+            duration_mins: cron.map(|_| 60),
+            applies_to_all: true,
+            created_at: at(1, 0, 0),
+            aliases: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn parses_cron_expressions_with_seconds() {
+        assert!(parse_cron(" 0 0 2 * * Sun ").is_ok());
+        assert!(parse_cron("0 2 * * Sun").unwrap_err().contains("is not a valid cron expression"));
+    }
+
+    #[test]
+    fn recurring_windows_last_their_duration() {
+        // may 5th 2024 is a sunday
+        let window = window(None, None, Some("0 0 2 * * Sun"));
+
+        assert_eq!(window.period_around(at(5, 2, 0)), Some((at(5, 2, 0), at(5, 3, 0))));
+        assert_eq!(window.period_around(at(5, 2, 30)), Some((at(5, 2, 0), at(5, 3, 0))));
+        assert_eq!(window.period_around(at(5, 3, 0)), Some((at(12, 2, 0), at(12, 3, 0))));
+        assert_eq!(window.period_around(at(4, 12, 0)), Some((at(5, 2, 0), at(5, 3, 0))));
+
+        assert!(window.is_active_at(at(5, 2, 59)));
+        assert!(!window.is_active_at(at(5, 3, 0)));
+        assert!(!window.is_active_at(at(5, 1, 59)));
+    }
+
+    #[test]
+    fn one_off_windows_are_over_once_they_end() {
+        let window = window(Some(at(5, 2, 0)), Some(at(5, 4, 0)), None);
+
+        assert_eq!(window.period_around(at(1, 0, 0)), Some((at(5, 2, 0), at(5, 4, 0))));
+        assert!(window.is_active_at(at(5, 3, 0)));
+        assert_eq!(window.period_around(at(5, 4, 0)), None);
+    }
+}
@@ -0,0 +1,873 @@
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{PgExecutor, PgPool};
+use tokio::time::{self, Duration};
+
+use crate::crypto::SecretCipher;
+use crate::flapping::FlapChange;
+use crate::incidents::{format_duration, Incident, IncidentKind, Transition};
+use crate::Website;
+
+mod discord;
+mod email;
+mod opsgenie;
+mod pagerduty;
+mod slack;
+mod teams;
+mod webhook;
+
+pub use email::{recipients as email_recipients, Mailer};
+
+/*
+/ how often a notification is sent before its delivery
+/ counts as failed, and how long to wait before the first
+/ retry. the wait doubles after every failed attempt
+ */
+const DELIVERY_ATTEMPTS: i32 = 5;
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/*
+/ how long chat apps and paging services get to accept a message
+ */
+const CHAT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/*
+/ the kinds of channels a notification can be sent to
+ */
+#[derive(sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelKind {
+    Webhook,
+    Email,
+    Slack,
+    Discord,
+    Teams,
+    PagerDuty,
+    Opsgenie,
+}
+
+/*
+/ somewhere notifications are sent to. monitors only notify
+/ the channels that are linked to them in website_channels.
+/ the url is where a webhook is posted to, or the mailto:
+/ list of recipients of an email channel. slack, discord
+/ and teams channels are the incoming webhook url the chat
+/ app gives out, pagerduty and opsgenie channels the api
+/ endpoint alerts are sent to
+ */
+#[derive(sqlx::FromRow, Serialize, Clone)]
+pub struct NotificationChannel {
+    pub id: i32,
+    pub name: String,
+    pub kind: ChannelKind,
+    pub url: String,
+    #[serde(skip)]
+    pub secret: Option<Vec<u8>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl NotificationChannel {
+    pub async fn all(db: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>("SELECT * FROM notification_channels ORDER BY name")
+            .fetch_all(db)
+            .await
+    }
+
+    pub async fn escalated_to(db: &PgPool, incident_id: i32) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            "SELECT notification_channels.* FROM notification_channels \
+            JOIN incident_escalations ON incident_escalations.channel_id = notification_channels.id \
+            WHERE incident_escalations.incident_id = $1"
+        )
+            .bind(incident_id)
+            .fetch_all(db)
+            .await
+    }
+}
+
+/*
+/ a channel linked to a monitor. an incident is escalated to
+/ the channel once it's been open for escalate_after_mins
+/ without being acknowledged, channels with no delay are
+/ told straight away
+ */
+#[derive(sqlx::FromRow, Serialize)]
+pub struct LinkedChannel {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub channel: NotificationChannel,
+    pub escalate_after_mins: i32,
+}
+
+impl LinkedChannel {
+    pub async fn for_website(db: &PgPool, website_id: i32) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            "SELECT notification_channels.*, website_channels.escalate_after_mins FROM notification_channels \
+            JOIN website_channels ON website_channels.channel_id = notification_channels.id \
+            WHERE website_channels.website_id = $1 \
+            ORDER BY website_channels.escalate_after_mins, notification_channels.name"
+        )
+            .bind(website_id)
+            .fetch_all(db)
+            .await
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    Down,
+    Up,
+    Flapping,
+    Stable,
+    CertificateExpiring,
+    CertificateRenewed,
+    Test,
+}
+
+impl NotificationEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Down => "down",
+            Self::Up => "up",
+            Self::Flapping => "flapping",
+            Self::Stable => "stable",
+            Self::CertificateExpiring => "certificate_expiring",
+            Self::CertificateRenewed => "certificate_renewed",
+            Self::Test => "test",
+        }
+    }
+}
+
+/*
+/ what's sent when a monitor goes down, comes back up, starts
+/ or stops flapping, or its certificate is about to expire or
+/ was renewed, this is also the json payload of webhook
+/ channels. it's stored as the payload of its delivery and
+/ read back from there to resume deliveries after a restart
+ */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Notification {
+    pub event: NotificationEvent,
+    #[serde(skip)]
+    pub website_id: Option<i32>,
+    pub alias: String,
+    pub url: String,
+    pub is_up: Option<bool>,
+    pub status: Option<i16>,
+    pub error: Option<String>,
+    pub incident_id: Option<i32>,
+    pub opened_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub duration_secs: Option<i64>,
+    pub link: Option<String>,
+    pub acknowledge_link: Option<String>,
+    pub sent_at: DateTime<Utc>,
+}
+
+impl Notification {
+    /*
+    / a notification about an open incident says the monitor
+    / is down, one about a resolved incident that it's back up,
+    / or the same about its certificate for certificate
+    / incidents. status is the status code of the check that
+    / caused it, the error is the cause of the incident and is
+    / only set while the incident is open. the link back to the
+    / monitor's page is only known if PUBLIC_URL is configured
+     */
+    pub fn new(website: &Website, incident: &Incident, status: Option<i16>, public_url: Option<&str>) -> Self {
+        let event = match (incident.kind, incident.resolved_at) {
+            (IncidentKind::Down, None) => NotificationEvent::Down,
+            (IncidentKind::Down, Some(_)) => NotificationEvent::Up,
+            (IncidentKind::Certificate, None) => NotificationEvent::CertificateExpiring,
+            (IncidentKind::Certificate, Some(_)) => NotificationEvent::CertificateRenewed,
+        };
+
+        let is_up = match incident.kind {
+            IncidentKind::Down => Some(incident.resolved_at.is_some()),
+            IncidentKind::Certificate => None,
+        };
+
+        Self {
+            event,
+            website_id: Some(website.id),
+            alias: website.alias.clone(),
+            url: website.url.clone(),
+            is_up,
+            status,
+            error: incident.resolved_at.is_none().then(|| incident.cause.clone()),
+            incident_id: Some(incident.id),
+            opened_at: incident.opened_at,
+            resolved_at: incident.resolved_at,
+            duration_secs: incident.resolved_at.map(|resolved_at| (resolved_at - incident.opened_at).num_seconds()),
+            link: public_url.map(|public_url| format!("{}/websites/{}", public_url.trim_end_matches('/'), website.alias)),
+            acknowledge_link: None,
+            sent_at: Utc::now(),
+        }
+    }
+
+    /*
+    / sent once when a monitor starts flapping, instead of its
+    / individual up and down notifications, and once more when
+    / it's stable again
+     */
+    pub fn flapping(website: &Website, change: &FlapChange, public_url: Option<&str>) -> Self {
+        let (event, is_up, error) = match change {
+            FlapChange::Started { state_changes, window_mins } => (
+                NotificationEvent::Flapping,
+                None,
+                Some(format!("went up and down {state_changes} times in the last {window_mins} minutes")),
+            ),
+            FlapChange::Stopped { is_up } => (NotificationEvent::Stable, Some(*is_up), None),
+        };
+
+        Self {
+            event,
+            website_id: Some(website.id),
+            alias: website.alias.clone(),
+            url: website.url.clone(),
+            is_up,
+            status: None,
+            error,
+            incident_id: None,
+            opened_at: website.flapping_since.unwrap_or_else(Utc::now),
+            resolved_at: None,
+            duration_secs: None,
+            link: public_url.map(|public_url| format!("{}/websites/{}", public_url.trim_end_matches('/'), website.alias)),
+            acknowledge_link: None,
+            sent_at: Utc::now(),
+        }
+    }
+
+    /*
+    / sent by the test button on channels.html to check that a
+    / channel is set up correctly, it isn't about any monitor
+     */
+    pub fn test(channel: &NotificationChannel, public_url: Option<&str>) -> Self {
+        Self {
+            event: NotificationEvent::Test,
+            website_id: None,
+            alias: channel.name.clone(),
+            url: channel.url.clone(),
+            is_up: None,
+            status: None,
+            error: None,
+            incident_id: None,
+            opened_at: Utc::now(),
+            resolved_at: None,
+            duration_secs: None,
+            link: public_url.map(str::to_owned),
+            acknowledge_link: None,
+            sent_at: Utc::now(),
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match self.event {
+            NotificationEvent::Down => format!("🔴 {} is down", self.alias),
+            NotificationEvent::Up => format!("🟢 {} is back up", self.alias),
+            NotificationEvent::Flapping => format!("🟠 {} is flapping", self.alias),
+            NotificationEvent::Stable => format!("🔵 {} stopped flapping", self.alias),
+            NotificationEvent::CertificateExpiring => format!("🟡 The certificate of {} expires soon", self.alias),
+            NotificationEvent::CertificateRenewed => format!("🟢 The certificate of {} was renewed", self.alias),
+            NotificationEvent::Test => format!("Test notification for {}", self.alias),
+        }
+    }
+
+    /*
+    / the title without the emoji, for paging services
+     */
+    pub fn summary(&self) -> String {
+        match self.event {
+            NotificationEvent::Down => format!("{} is down", self.alias),
+            NotificationEvent::Up => format!("{} is back up", self.alias),
+            NotificationEvent::Flapping => format!("{} is flapping", self.alias),
+            NotificationEvent::Stable => format!("{} stopped flapping", self.alias),
+            NotificationEvent::CertificateExpiring => format!("The certificate of {} expires soon", self.alias),
+            NotificationEvent::CertificateRenewed => format!("The certificate of {} was renewed", self.alias),
+            NotificationEvent::Test => format!("Test notification for {}", self.alias),
+        }
+    }
+
+    /*
+    / paging services group the alerts of one incident by
+    / this key, so the alert opened when a monitor goes down
+    / is the one resolved when it comes back up. the same goes
+    / for a monitor that starts and stops flapping, keyed by its
+    / id since it can be renamed while it's flapping
+     */
+    pub fn dedup_key(&self) -> String {
+        match (self.incident_id, self.website_id, self.event) {
+            (Some(incident_id), _, _) => format!("uptime-monitor-incident-{incident_id}"),
+            (None, Some(website_id), NotificationEvent::Flapping | NotificationEvent::Stable) => {
+                format!("uptime-monitor-flapping-{website_id}")
+            }
+            (None, _, _) => format!("uptime-monitor-test-{}", self.sent_at.timestamp()),
+        }
+    }
+
+    /*
+    / the colour chat messages are highlighted with, as rgb
+     */
+    pub fn color(&self) -> u32 {
+        match self.event {
+            NotificationEvent::Down => 0xE01E5A,
+            NotificationEvent::Up => 0x2EB67D,
+            NotificationEvent::Flapping => 0xF2A33A,
+            NotificationEvent::Stable => 0x3B82F6,
+            NotificationEvent::CertificateExpiring => 0xECB22E,
+            NotificationEvent::CertificateRenewed => 0x2EB67D,
+            NotificationEvent::Test => 0x808080,
+        }
+    }
+
+    /*
+    / the details chat messages list below their title
+     */
+    pub fn facts(&self) -> Vec<(&'static str, String)> {
+        let mut facts = vec![("URL", self.url.clone())];
+
+        if let Some(status) = self.status {
+            facts.push(("Status", status.to_string()));
+        }
+        if let Some(error) = &self.error {
+            facts.push(("Error", error.clone()));
+        }
+        match self.event {
+            NotificationEvent::Down => {
+                facts.push(("Down since", self.opened_at.to_rfc2822()));
+                if let Some(acknowledge_link) = &self.acknowledge_link {
+                    facts.push(("Acknowledge", acknowledge_link.clone()));
+                }
+            }
+            NotificationEvent::CertificateExpiring => {
+                if let Some(acknowledge_link) = &self.acknowledge_link {
+                    facts.push(("Acknowledge", acknowledge_link.clone()));
+                }
+            }
+            NotificationEvent::Up => {
+                if let Some(duration_secs) = self.duration_secs {
+                    facts.push(("Down for", format_duration(duration_secs)));
+                }
+            }
+            NotificationEvent::Stable => {
+                let state = if self.is_up == Some(true) { "up" } else { "down" };
+                facts.push(("State", state.to_owned()));
+            }
+            NotificationEvent::Flapping | NotificationEvent::CertificateRenewed | NotificationEvent::Test => {}
+        }
+
+        facts
+    }
+}
+
+/*
+/ why sending a notification failed, with the status code
+/ the channel answered with if it answered at all
+ */
+#[derive(Debug)]
+pub struct DeliveryError {
+    pub status: Option<i16>,
+    pub message: String,
+}
+
+impl From<reqwest::Error> for DeliveryError {
+    fn from(e: reqwest::Error) -> Self {
+        Self {
+            status: e.status().map(|status| status.as_u16() as i16),
+            message: e.to_string(),
+        }
+    }
+}
+
+/*
+/ an attempt at notifying a channel, as shown on channels.html
+ */
+#[derive(sqlx::FromRow, Serialize)]
+pub struct Delivery {
+    pub id: i32,
+    pub channel_name: String,
+    pub alias: String,
+    pub event: String,
+    pub status: String,
+    pub attempts: i32,
+    pub response_status: Option<i16>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+impl Delivery {
+    pub async fn recent(db: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as::<_, Self>(
+            "SELECT notification_deliveries.id, notification_channels.name AS channel_name, websites.alias, \
+            notification_deliveries.event, notification_deliveries.status, notification_deliveries.attempts, \
+            notification_deliveries.response_status, notification_deliveries.error, \
+            notification_deliveries.created_at, notification_deliveries.delivered_at \
+            FROM notification_deliveries \
+            JOIN notification_channels ON notification_channels.id = notification_deliveries.channel_id \
+            JOIN websites ON websites.id = notification_deliveries.website_id \
+            ORDER BY notification_deliveries.created_at DESC \
+            LIMIT 50"
+        )
+            .fetch_all(db)
+            .await
+    }
+}
+
+/*
+/ sends notifications to the channels of a monitor when it
+/ changes state. every channel is delivered to in its own
+/ task so a slow or failing channel doesn't hold up checks
+/ or the other channels, and every delivery is logged in
+/ notification_deliveries
+ */
+#[derive(Clone)]
+pub struct Notifier {
+    client: Client,
+    db: PgPool,
+    cipher: Option<SecretCipher>,
+    mailer: Option<Mailer>,
+    public_url: Option<String>,
+}
+
+impl Notifier {
+    pub fn new(db: PgPool, cipher: Option<SecretCipher>, mailer: Option<Mailer>, public_url: Option<String>) -> Self {
+        Self {
+            client: Client::new(),
+            db,
+            cipher,
+            mailer,
+            public_url,
+        }
+    }
+
+    /*
+    / a new incident is escalated straight away to the channels
+    / that don't wait, a resolved one is announced to every
+    / channel that was told about it
+     */
+    pub async fn notify(&self, website: &Website, transition: &Transition, status: Option<i16>) {
+        let incident = match transition {
+            Transition::Opened(incident) => {
+                println!("{}: {}, escalating incident {}", website.alias, incident.cause, incident.id);
+                return self.escalate().await;
+            }
+            Transition::Resolved(incident) => incident,
+        };
+
+        let channels = match NotificationChannel::escalated_to(&self.db, incident.id).await {
+            Ok(channels) => channels,
+            Err(e) => {
+                println!("Failed to fetch notification channels of {}: {e}", website.alias);
+                return;
+            }
+        };
+
+        let notification = self.notification(website, incident, status);
+
+        for channel in channels {
+            self.spawn_delivery(channel, website.id, notification.clone());
+        }
+    }
+
+    /*
+    / a flapping monitor tells all of its channels once, and
+    / once more when it's stable again
+     */
+    pub async fn notify_flapping(&self, website: &Website, change: &FlapChange) {
+        let channels = match LinkedChannel::for_website(&self.db, website.id).await {
+            Ok(channels) => channels,
+            Err(e) => {
+                println!("Failed to fetch notification channels of {}: {e}", website.alias);
+                return;
+            }
+        };
+
+        let notification = Notification::flapping(website, change, self.public_url.as_deref());
+
+        for linked in channels {
+            self.spawn_delivery(linked.channel, website.id, notification.clone());
+        }
+    }
+
+    /*
+    / notify every channel whose escalation delay has passed for
+    / an open incident that nobody acknowledged yet. flapping
+    / monitors aren't escalated until they're stable, and
+    / monitors in maintenance not until the window is over,
+    / going by whether their latest check was tagged. paused
+    / monitors aren't escalated until they're resumed. this runs
+    / on every scheduler tick, and since escalations are stored
+    / in incident_escalations a restart neither loses pending
+    / escalations nor sends the same one twice
+     */
+    pub async fn escalate(&self) {
+        let due = sqlx::query_as::<_, (i32, i32)>(
+            "SELECT incidents.id, website_channels.channel_id FROM incidents \
+            JOIN website_channels ON website_channels.website_id = incidents.website_id \
+            JOIN websites ON websites.id = incidents.website_id \
+            WHERE incidents.resolved_at IS NULL AND incidents.acknowledged_at IS NULL \
+            AND websites.flapping_since IS NULL AND websites.paused_at IS NULL \
+            AND NOT coalesce(( \
+                SELECT in_maintenance FROM logs WHERE logs.website_id = incidents.website_id \
+                ORDER BY created_at DESC LIMIT 1 \
+            ), false) \
+            AND incidents.opened_at + make_interval(mins => website_channels.escalate_after_mins) <= current_timestamp \
+            AND NOT EXISTS (SELECT 1 FROM incident_escalations \
+                WHERE incident_escalations.incident_id = incidents.id \
+                AND incident_escalations.channel_id = website_channels.channel_id)"
+        )
+            .fetch_all(&self.db)
+            .await;
+
+        let due = match due {
+            Ok(due) => due,
+            Err(e) => {
+                println!("Failed to fetch due escalations: {e}");
+                return;
+            }
+        };
+
+        for (incident_id, channel_id) in due {
+            if let Err(e) = self.start_escalation(incident_id, channel_id).await {
+                println!("Failed to escalate incident {incident_id} to channel {channel_id}: {e}");
+            }
+        }
+    }
+
+    /*
+    / an escalation is only stored together with its pending
+    / delivery, so one that's claimed is either sent now or
+    / resumed by resume_deliveries after a restart
+     */
+    async fn start_escalation(&self, incident_id: i32, channel_id: i32) -> Result<(), sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+
+        // whoever stores the escalation first sends it
+        let claimed: Option<i32> = sqlx::query_scalar(
+            "INSERT INTO incident_escalations (incident_id, channel_id) VALUES ($1, $2) \
+            ON CONFLICT DO NOTHING RETURNING incident_id"
+        )
+            .bind(incident_id)
+            .bind(channel_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        if claimed.is_none() {
+            return Ok(());
+        }
+
+        let Some(incident) = Incident::by_id(&mut *tx, incident_id).await? else {
+            return Ok(());
+        };
+
+        let website = sqlx::query_as::<_, Website>("SELECT * FROM websites WHERE id = $1")
+            .bind(incident.website_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let channel = sqlx::query_as::<_, NotificationChannel>("SELECT * FROM notification_channels WHERE id = $1")
+            .bind(channel_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let notification = self.notification(&website, &incident, incident.first_status);
+        let delivery_id = queue_delivery(&mut *tx, channel.id, website.id, &notification).await?;
+        tx.commit().await?;
+
+        self.spawn_attempts(delivery_id, channel, notification);
+
+        Ok(())
+    }
+
+    /*
+    / deliveries that were still pending when the app stopped,
+    / e.g. escalations that were stored but not sent yet, are
+    / sent again when it starts
+     */
+    pub async fn resume_deliveries(&self) {
+        let pending = sqlx::query_as::<_, (i32, i32, Value)>(
+            "SELECT notification_deliveries.id, notification_deliveries.website_id, notification_deliveries.payload \
+            FROM notification_deliveries WHERE status = 'pending' ORDER BY created_at"
+        )
+            .fetch_all(&self.db)
+            .await;
+
+        let pending = match pending {
+            Ok(pending) => pending,
+            Err(e) => {
+                println!("Failed to fetch pending deliveries: {e}");
+                return;
+            }
+        };
+
+        for (delivery_id, website_id, payload) in pending {
+            if let Err(e) = self.resume_delivery(delivery_id, website_id, payload).await {
+                println!("Failed to resume delivery {delivery_id}: {e}");
+            }
+        }
+    }
+
+    async fn resume_delivery(&self, delivery_id: i32, website_id: i32, payload: Value) -> Result<(), sqlx::Error> {
+        let channel = sqlx::query_as::<_, NotificationChannel>(
+            "SELECT notification_channels.* FROM notification_channels \
+            JOIN notification_deliveries ON notification_deliveries.channel_id = notification_channels.id \
+            WHERE notification_deliveries.id = $1"
+        )
+            .bind(delivery_id)
+            .fetch_one(&self.db)
+            .await?;
+
+        match stored_notification(payload, website_id) {
+            Ok(notification) => self.spawn_attempts(delivery_id, channel, notification),
+            Err(e) => {
+                let error = DeliveryError { status: None, message: format!("couldn't read the stored notification: {e}") };
+                self.finish_delivery(delivery_id, 0, Err(error)).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /*
+    / notifications about open incidents carry a signed link to
+    / acknowledge them, if we know where we're reachable
+     */
+    fn notification(&self, website: &Website, incident: &Incident, status: Option<i16>) -> Notification {
+        let mut notification = Notification::new(website, incident, status, self.public_url.as_deref());
+
+        if let (NotificationEvent::Down | NotificationEvent::CertificateExpiring, Some(public_url), Some(cipher)) =
+            (notification.event, &self.public_url, &self.cipher) {
+            notification.acknowledge_link = Some(format!(
+                "{}/incidents/{}/acknowledge?signature={}",
+                public_url.trim_end_matches('/'),
+                incident.id,
+                cipher.sign(&Incident::acknowledge_message(incident.id))
+            ));
+        }
+
+        notification
+    }
+
+    fn spawn_delivery(&self, channel: NotificationChannel, website_id: i32, notification: Notification) {
+        let notifier = self.clone();
+
+        tokio::spawn(async move {
+            let delivered = match queue_delivery(&notifier.db, channel.id, website_id, &notification).await {
+                Ok(delivery_id) => notifier.deliver(delivery_id, &channel, &notification).await,
+                Err(e) => Err(e),
+            };
+
+            if let Err(e) = delivered {
+                println!("Failed to log delivery to channel {}: {e}", channel.name);
+            }
+        });
+    }
+
+    /*
+    / deliver a notification whose delivery is already queued
+     */
+    fn spawn_attempts(&self, delivery_id: i32, channel: NotificationChannel, notification: Notification) {
+        let notifier = self.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = notifier.deliver(delivery_id, &channel, &notification).await {
+                println!("Failed to log delivery to channel {}: {e}", channel.name);
+            }
+        });
+    }
+
+    async fn deliver(
+        &self,
+        delivery_id: i32,
+        channel: &NotificationChannel,
+        notification: &Notification,
+    ) -> Result<(), sqlx::Error> {
+        let mut attempts = 0;
+        let mut retry_delay = FIRST_RETRY_DELAY;
+
+        let result = match self.secret(channel) {
+            Err(e) => Err(e),
+            Ok(secret) => loop {
+                attempts += 1;
+                let result = self.send(channel, secret.as_deref(), notification).await;
+
+                if result.is_ok() || attempts >= DELIVERY_ATTEMPTS {
+                    break result;
+                }
+
+                time::sleep(retry_delay).await;
+                retry_delay *= 2;
+            },
+        };
+
+        self.finish_delivery(delivery_id, attempts, result).await
+    }
+
+    async fn finish_delivery(
+        &self,
+        delivery_id: i32,
+        attempts: i32,
+        result: Result<(), DeliveryError>,
+    ) -> Result<(), sqlx::Error> {
+        let (status, response_status, error) = match result {
+            Ok(()) => ("delivered", None, None),
+            Err(e) => ("failed", e.status, Some(e.message)),
+        };
+
+        sqlx::query(
+            "UPDATE notification_deliveries SET status = $2, attempts = $3, response_status = $4, error = $5, \
+            delivered_at = CASE WHEN $2 = 'delivered' THEN current_timestamp END \
+            WHERE id = $1"
+        )
+            .bind(delivery_id)
+            .bind(status)
+            .bind(attempts)
+            .bind(response_status)
+            .bind(error)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    /*
+    / send a test notification once, without retries and
+    / without logging it as a delivery
+     */
+    pub async fn send_test(&self, channel: &NotificationChannel) -> Result<(), DeliveryError> {
+        let secret = self.secret(channel)?;
+        let notification = Notification::test(channel, self.public_url.as_deref());
+        self.send(channel, secret.as_deref(), &notification).await
+    }
+
+    fn secret(&self, channel: &NotificationChannel) -> Result<Option<String>, DeliveryError> {
+        let Some(encrypted) = &channel.secret else {
+            return Ok(None);
+        };
+
+        let decrypted = match &self.cipher {
+            Some(cipher) => cipher.decrypt(encrypted),
+            None => Err("MONITOR_SECRETS_KEY is not configured, can't decrypt the channel secret".to_owned()),
+        };
+
+        decrypted
+            .map(Some)
+            .map_err(|message| DeliveryError { status: None, message })
+    }
+
+    async fn send(
+        &self,
+        channel: &NotificationChannel,
+        secret: Option<&str>,
+        notification: &Notification,
+    ) -> Result<(), DeliveryError> {
+        match channel.kind {
+            ChannelKind::Webhook => webhook::send(&self.client, &channel.url, secret, notification).await,
+            ChannelKind::Email => email::send(self.mailer.as_ref(), &channel.url, notification).await,
+            ChannelKind::Slack => post_json(&self.client, &channel.url, &slack::message(notification)).await,
+            ChannelKind::Discord => post_json(&self.client, &channel.url, &discord::message(notification)).await,
+            ChannelKind::Teams => post_json(&self.client, &channel.url, &teams::message(notification)).await,
+            ChannelKind::PagerDuty => pagerduty::send(&self.client, &channel.url, secret, notification).await,
+            ChannelKind::Opsgenie => opsgenie::send(&self.client, &channel.url, secret, notification).await,
+        }
+    }
+}
+
+/*
+/ store a delivery as pending before it's attempted
+ */
+async fn queue_delivery<'e>(
+    db: impl PgExecutor<'e>,
+    channel_id: i32,
+    website_id: i32,
+    notification: &Notification,
+) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar(
+        "INSERT INTO notification_deliveries (channel_id, website_id, incident_id, event, payload) \
+        VALUES ($1, $2, $3, $4, $5) RETURNING id"
+    )
+        .bind(channel_id)
+        .bind(website_id)
+        .bind(notification.incident_id)
+        .bind(notification.event.as_str())
+        .bind(serde_json::to_value(notification).unwrap_or_default())
+        .fetch_one(db)
+        .await
+}
+
+/*
+/ the website id isn't part of the payload, it's stored
+/ next to it
+ */
+fn stored_notification(payload: Value, website_id: i32) -> Result<Notification, serde_json::Error> {
+    let mut notification: Notification = serde_json::from_value(payload)?;
+    notification.website_id = Some(website_id);
+    Ok(notification)
+}
+
+/*
+/ chat apps and pagerduty take their messages as json posted
+/ to the url they gave out
+ */
+async fn post_json(client: &Client, url: &str, message: &Value) -> Result<(), DeliveryError> {
+    client
+        .post(url)
+        .timeout(CHAT_TIMEOUT)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(message.to_string())
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub fn notification(event: NotificationEvent, incident_id: Option<i32>, website_id: Option<i32>) -> Notification {
+        Notification {
+            event,
+            website_id,
+            alias: "api".to_owned(),
+            url: "https://api.example.com/health".to_owned(),
+            is_up: Some(false),
+            status: Some(503),
+            error: Some("status 503".to_owned()),
+            incident_id,
+            opened_at: Utc::now(),
+            resolved_at: None,
+            duration_secs: None,
+            link: None,
+            acknowledge_link: None,
+            sent_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn dedup_keys_follow_the_incident_or_the_flapping_monitor() {
+        assert_eq!(notification(NotificationEvent::Down, Some(41), Some(7)).dedup_key(), "uptime-monitor-incident-41");
+        assert_eq!(notification(NotificationEvent::Up, Some(41), Some(7)).dedup_key(), "uptime-monitor-incident-41");
+        assert_eq!(notification(NotificationEvent::Flapping, None, Some(7)).dedup_key(), "uptime-monitor-flapping-7");
+        assert_eq!(notification(NotificationEvent::Stable, None, Some(7)).dedup_key(), "uptime-monitor-flapping-7");
+        assert!(notification(NotificationEvent::Test, None, None).dedup_key().starts_with("uptime-monitor-test-"));
+    }
+
+    #[test]
+    fn stored_notifications_keep_their_dedup_key() {
+        let flapping = notification(NotificationEvent::Flapping, None, Some(7));
+        let payload = serde_json::to_value(&flapping).unwrap();
+
+        assert!(payload.get("website_id").is_none());
+
+        let stored = stored_notification(payload, 7).unwrap();
+        assert_eq!(stored.event, NotificationEvent::Flapping);
+        assert_eq!(stored.dedup_key(), flapping.dedup_key());
+    }
+}
@@ -0,0 +1,24 @@
+use serde_json::{json, Value};
+
+use super::Notification;
+
+/*
+/ a discord webhook message with a single colour-coded embed
+ */
+pub fn message(notification: &Notification) -> Value {
+    let fields: Vec<Value> = notification
+        .facts()
+        .into_iter()
+        .map(|(name, value)| json!({ "name": name, "value": value, "inline": name != "Error" }))
+        .collect();
+
+    json!({
+        "embeds": [{
+            "title": notification.title(),
+            "url": notification.link,
+            "color": notification.color(),
+            "fields": fields,
+            "timestamp": notification.sent_at.to_rfc3339(),
+        }],
+    })
+}
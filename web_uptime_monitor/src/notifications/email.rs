@@ -0,0 +1,300 @@
+use askama::Template;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use reqwest::Url;
+use shuttle_runtime::SecretStore;
+
+use super::{DeliveryError, Notification, NotificationEvent};
+
+/*
+/ how to talk to the smtp server. starttls upgrades a plain
+/ connection (usually port 587), tls connects with implicit
+/ tls (usually port 465) and none is only meant for a local
+/ smtp sink while developing
+ */
+enum SmtpSecurity {
+    StartTls,
+    Tls,
+    None,
+}
+
+/*
+/ sends the emails of email channels through the smtp server
+/ configured in Secrets.toml, email channels can't deliver
+/ anything without one
+ */
+#[derive(Clone)]
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl Mailer {
+    pub fn from_secrets(secrets: &SecretStore) -> Option<Self> {
+        let host = secrets.get("SMTP_HOST")?;
+
+        match Self::build(secrets, &host) {
+            Ok(mailer) => Some(mailer),
+            Err(e) => {
+                println!("Invalid SMTP settings, email notifications are disabled: {e}");
+                None
+            }
+        }
+    }
+
+    fn build(secrets: &SecretStore, host: &str) -> Result<Self, String> {
+        let security = match secrets.get("SMTP_SECURITY").as_deref().unwrap_or("starttls") {
+            "starttls" => SmtpSecurity::StartTls,
+            "tls" => SmtpSecurity::Tls,
+            "none" => SmtpSecurity::None,
+            other => return Err(format!("SMTP_SECURITY must be starttls, tls or none, not {other:?}")),
+        };
+
+        let port = match secrets.get("SMTP_PORT") {
+            Some(port) => Some(port.parse().map_err(|_| format!("SMTP_PORT {port:?} is not a valid port"))?),
+            None => None,
+        };
+
+        let credentials = match (secrets.get("SMTP_USERNAME"), secrets.get("SMTP_PASSWORD")) {
+            (Some(username), Some(password)) => Some(Credentials::new(username, password)),
+            _ => None,
+        };
+
+        let from = secrets
+            .get("SMTP_FROM")
+            .ok_or("SMTP_FROM is required")?
+            .parse()
+            .map_err(|e| format!("SMTP_FROM is not a valid address: {e}"))?;
+
+        Self::new(host, port, security, credentials, from)
+    }
+
+    /*
+    / the port defaults to the one that goes with the security
+     */
+    fn new(
+        host: &str,
+        port: Option<u16>,
+        security: SmtpSecurity,
+        credentials: Option<Credentials>,
+        from: Mailbox,
+    ) -> Result<Self, String> {
+        let mut transport = match security {
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+            SmtpSecurity::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)),
+        }
+        .map_err(|e| e.to_string())?;
+
+        if let Some(port) = port {
+            transport = transport.port(port);
+        }
+
+        if let Some(credentials) = credentials {
+            transport = transport.credentials(credentials);
+        }
+
+        Ok(Self {
+            transport: transport.build(),
+            from,
+        })
+    }
+}
+
+#[derive(Template)]
+#[template(path = "email_subject.txt")]
+struct EmailSubject<'a> {
+    notification: &'a Notification,
+}
+
+#[derive(Template)]
+#[template(path = "email_body.txt")]
+struct EmailBody<'a> {
+    notification: &'a Notification,
+}
+
+/*
+/ email channels are written as mailto:one@example.com,two@example.com
+ */
+pub fn recipients(url: &str) -> Result<Vec<Mailbox>, String> {
+    let url = Url::parse(url).map_err(|e| format!("{url:?} is not a valid mailto url: {e}"))?;
+
+    if url.scheme() != "mailto" {
+        return Err(format!("{url} should look like mailto:someone@example.com"));
+    }
+
+    let recipients = url
+        .path()
+        .split(',')
+        .filter(|address| !address.trim().is_empty())
+        .map(|address| address.trim().parse().map_err(|_| format!("{address:?} is not a valid email address")))
+        .collect::<Result<Vec<Mailbox>, _>>()?;
+
+    if recipients.is_empty() {
+        return Err("at least one recipient is required".to_owned());
+    }
+
+    Ok(recipients)
+}
+
+pub async fn send(mailer: Option<&Mailer>, url: &str, notification: &Notification) -> Result<(), DeliveryError> {
+    let error = |message: String| DeliveryError { status: None, message };
+
+    let mailer = mailer.ok_or_else(|| error("SMTP_HOST is not configured, can't send emails".to_owned()))?;
+    let subject = EmailSubject { notification }.render().map_err(|e| error(e.to_string()))?;
+    let body = EmailBody { notification }.render().map_err(|e| error(e.to_string()))?;
+
+    let mut message = Message::builder()
+        .from(mailer.from.clone())
+        .subject(subject.trim())
+        .header(ContentType::TEXT_PLAIN);
+
+    for recipient in recipients(url).map_err(error)? {
+        message = message.to(recipient);
+    }
+
+    let message = message.body(body).map_err(|e| error(e.to_string()))?;
+
+    mailer.transport.send(message).await.map_err(|e| error(e.to_string()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    fn notification(event: NotificationEvent) -> Notification {
+        let opened_at = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+
+        Notification {
+            event,
+            website_id: Some(1),
+            alias: "api".to_owned(),
+            url: "https://api.example.com".to_owned(),
+            is_up: Some(event == NotificationEvent::Up),
+            status: Some(503),
+            error: (event == NotificationEvent::Down).then(|| "status 503".to_owned()),
+            incident_id: Some(7),
+            opened_at,
+            resolved_at: (event == NotificationEvent::Up).then(|| Utc.with_ymd_and_hms(2024, 5, 1, 12, 5, 0).unwrap()),
+            duration_secs: (event == NotificationEvent::Up).then_some(300),
+            link: Some("https://status.example.com/websites/api".to_owned()),
+            acknowledge_link: (event == NotificationEvent::Down)
+                .then(|| "https://status.example.com/incidents/7/acknowledge?signature=abc".to_owned()),
+            sent_at: opened_at,
+        }
+    }
+
+    /*
+    / an smtp server that accepts one message and hands back
+    / what was sent after DATA
+     */
+    async fn smtp_sink() -> (u16, oneshot::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, receiver) = oneshot::channel();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            let mut sender = Some(sender);
+
+            writer.write_all(b"220 localhost\r\n").await.unwrap();
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                let reply: &[u8] = match line.to_uppercase().split(' ').next().unwrap_or_default() {
+                    "DATA" => {
+                        writer.write_all(b"354 end with .\r\n").await.unwrap();
+
+                        let mut data = Vec::new();
+                        while let Ok(Some(line)) = lines.next_line().await {
+                            if line == "." {
+                                break;
+                            }
+                            data.push(line);
+                        }
+                        if let Some(sender) = sender.take() {
+                            let _ = sender.send(data.join("\n"));
+                        }
+
+                        b"250 queued\r\n"
+                    }
+                    "QUIT" => {
+                        writer.write_all(b"221 bye\r\n").await.unwrap();
+                        return;
+                    }
+                    _ => b"250 ok\r\n",
+                };
+
+                writer.write_all(reply).await.unwrap();
+            }
+        });
+
+        (port, receiver)
+    }
+
+    #[test]
+    fn renders_subjects() {
+        let subject = |event| EmailSubject { notification: &notification(event) }.render().unwrap();
+
+        assert_eq!(subject(NotificationEvent::Down), "[DOWN] api is down");
+        assert_eq!(subject(NotificationEvent::Up), "[UP] api is back up");
+        assert_eq!(subject(NotificationEvent::Flapping), "[FLAPPING] api is flapping");
+        assert_eq!(subject(NotificationEvent::Stable), "[STABLE] api stopped flapping");
+        assert_eq!(subject(NotificationEvent::CertificateExpiring), "[CERTIFICATE] The certificate of api expires soon");
+        assert_eq!(subject(NotificationEvent::CertificateRenewed), "[RENEWED] The certificate of api was renewed");
+        assert_eq!(subject(NotificationEvent::Test), "[TEST] Test notification from Shuttle Status Monitor");
+    }
+
+    #[test]
+    fn renders_bodies() {
+        let body = |event| EmailBody { notification: &notification(event) }.render().unwrap();
+
+        let down = body(NotificationEvent::Down);
+        assert!(down.starts_with("api (https://api.example.com) went down at 2024-05-01 12:00:00 UTC."));
+        assert!(down.contains("Status: 503\nError: status 503"));
+        assert!(down.contains("https://status.example.com/incidents/7/acknowledge?signature=abc"));
+        assert!(down.ends_with("https://status.example.com/websites/api\n\n--\nShuttle Status Monitor"));
+
+        let up = body(NotificationEvent::Up);
+        assert!(up.contains("It was down from 2024-05-01 12:00:00 UTC until 2024-05-01 12:05:00 UTC."));
+        assert!(!up.contains("acknowledge"));
+
+        let stable = body(NotificationEvent::Stable);
+        assert!(stable.contains("stopped flapping and is down."));
+    }
+
+    #[test]
+    fn parses_recipients() {
+        let recipients = recipients("mailto:ops@example.com, dev@example.com").unwrap();
+
+        assert_eq!(recipients.len(), 2);
+        assert_eq!(recipients[1].email.to_string(), "dev@example.com");
+        assert!(super::recipients("mailto:").is_err());
+        assert!(super::recipients("https://example.com").is_err());
+        assert!(super::recipients("mailto:not an address").is_err());
+    }
+
+    #[tokio::test]
+    async fn sends_through_an_smtp_sink() {
+        let (port, received) = smtp_sink().await;
+        let from = "Status Monitor <status@example.com>".parse().unwrap();
+        let mailer = Mailer::new("127.0.0.1", Some(port), SmtpSecurity::None, None, from).unwrap();
+
+        send(Some(&mailer), "mailto:ops@example.com", &notification(NotificationEvent::Down)).await.unwrap();
+
+        let message = received.await.unwrap();
+        assert!(message.contains("Subject: [DOWN] api is down"));
+        assert!(message.contains("To: ops@example.com"));
+        assert!(message.contains("api (https://api.example.com) went down at 2024-05-01 12:00:00 UTC."));
+    }
+}
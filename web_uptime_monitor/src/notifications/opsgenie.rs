@@ -0,0 +1,146 @@
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Client, Url};
+use serde_json::{json, Map, Value};
+
+use super::{DeliveryError, Notification, NotificationEvent, CHAT_TIMEOUT};
+
+/*
+/ opsgenie's alert api. a monitor going down creates an alert
+/ with the dedup key as its alias and coming back up closes
+/ the alert with that alias, the same goes for expiring
+/ certificates, which are sent with a lower priority. the
+/ channel url is the alerts endpoint, usually
+/ https://api.opsgenie.com/v2/alerts, and its secret is the
+/ api key of the integration
+ */
+pub async fn send(client: &Client, url: &str, api_key: Option<&str>, notification: &Notification)
+    -> Result<(), DeliveryError> {
+    let api_key = api_key.ok_or_else(|| DeliveryError {
+        status: None,
+        message: "opsgenie channels need an api key".to_owned(),
+    })?;
+
+    match notification.event {
+        NotificationEvent::Down | NotificationEvent::Flapping | NotificationEvent::CertificateExpiring => {
+            create(client, url, api_key, notification).await
+        }
+        NotificationEvent::Up | NotificationEvent::Stable | NotificationEvent::CertificateRenewed => {
+            close(client, url, api_key, notification).await
+        }
+        // a test opens an alert and closes it again straight away
+        NotificationEvent::Test => {
+            create(client, url, api_key, notification).await?;
+            close(client, url, api_key, notification).await
+        }
+    }
+}
+
+async fn create(client: &Client, url: &str, api_key: &str, notification: &Notification) -> Result<(), DeliveryError> {
+    post(client, url, api_key, &alert(notification)).await
+}
+
+fn alert(notification: &Notification) -> Value {
+    let details: Map<String, Value> = notification
+        .facts()
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), Value::String(value)))
+        .collect();
+
+    let description = notification
+        .facts()
+        .into_iter()
+        .map(|(name, value)| format!("{name}: {value}"))
+        .chain(notification.link.clone())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let priority = match notification.event {
+        NotificationEvent::CertificateExpiring => "P3",
+        _ => "P1",
+    };
+
+    json!({
+        "message": notification.summary(),
+        "alias": notification.dedup_key(),
+        "description": description,
+        "details": details,
+        "source": "Shuttle Status Monitor",
+        "priority": priority,
+    })
+}
+
+async fn close(client: &Client, url: &str, api_key: &str, notification: &Notification) -> Result<(), DeliveryError> {
+    let note = json!({
+        "source": "Shuttle Status Monitor",
+        "note": notification.summary(),
+    });
+
+    post(client, close_url(url, &notification.dedup_key())?.as_str(), api_key, &note).await
+}
+
+/*
+/ the alias goes into the path, so it's escaped like any other path segment
+ */
+fn close_url(url: &str, alias: &str) -> Result<Url, DeliveryError> {
+    let mut close_url = Url::parse(url).map_err(|e| DeliveryError {
+        status: None,
+        message: format!("{url:?} is not a valid opsgenie url: {e}"),
+    })?;
+    close_url
+        .path_segments_mut()
+        .map_err(|_| DeliveryError {
+            status: None,
+            message: format!("{url:?} is not a valid opsgenie url"),
+        })?
+        .pop_if_empty()
+        .push(alias)
+        .push("close");
+    close_url.query_pairs_mut().append_pair("identifierType", "alias");
+
+    Ok(close_url)
+}
+
+async fn post(client: &Client, url: &str, api_key: &str, body: &Value) -> Result<(), DeliveryError> {
+    client
+        .post(url)
+        .timeout(CHAT_TIMEOUT)
+        .header(AUTHORIZATION, format!("GenieKey {api_key}"))
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::notification;
+    use super::*;
+
+    #[test]
+    fn closes_alerts_by_their_escaped_alias() {
+        let url = close_url("https://api.opsgenie.com/v2/alerts/", "uptime monitor/incident?41").unwrap();
+
+        assert_eq!(
+            url.as_str(),
+            "https://api.opsgenie.com/v2/alerts/uptime%20monitor%2Fincident%3F41/close?identifierType=alias"
+        );
+        assert_eq!(
+            close_url("https://api.eu.opsgenie.com/v2/alerts", "uptime-monitor-incident-41").unwrap().as_str(),
+            "https://api.eu.opsgenie.com/v2/alerts/uptime-monitor-incident-41/close?identifierType=alias"
+        );
+        assert!(close_url("not a url", "uptime-monitor-incident-41").is_err());
+    }
+
+    #[test]
+    fn certificate_alerts_have_a_lower_priority() {
+        let down = alert(&notification(NotificationEvent::Down, Some(41), Some(7)));
+        let certificate = alert(&notification(NotificationEvent::CertificateExpiring, Some(42), Some(7)));
+
+        assert_eq!(down["priority"], "P1");
+        assert_eq!(down["alias"], "uptime-monitor-incident-41");
+        assert_eq!(certificate["priority"], "P3");
+    }
+}
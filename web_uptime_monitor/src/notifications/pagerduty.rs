@@ -0,0 +1,110 @@
+use reqwest::Client;
+use serde_json::{json, Map, Value};
+
+use super::{post_json, DeliveryError, Notification, NotificationEvent};
+
+/*
+/ pagerduty events v2. a monitor going down triggers an alert
+/ and coming back up resolves it, both use the same dedup key
+/ so pagerduty closes the page on its own. expiring
+/ certificates trigger a warning that's resolved once the
+/ certificate is renewed. the channel url is the events
+/ endpoint, usually https://events.pagerduty.com/v2/enqueue,
+/ and its secret is the integration's routing key
+ */
+pub async fn send(client: &Client, url: &str, routing_key: Option<&str>, notification: &Notification)
+    -> Result<(), DeliveryError> {
+    let routing_key = routing_key.ok_or_else(|| DeliveryError {
+        status: None,
+        message: "pagerduty channels need a routing key".to_owned(),
+    })?;
+
+    match notification.event {
+        NotificationEvent::Down | NotificationEvent::Flapping | NotificationEvent::CertificateExpiring => {
+            post_json(client, url, &trigger(routing_key, notification)).await
+        }
+        NotificationEvent::Up | NotificationEvent::Stable | NotificationEvent::CertificateRenewed => {
+            post_json(client, url, &resolve(routing_key, notification)).await
+        }
+        // a test opens an alert and closes it again straight away
+        NotificationEvent::Test => {
+            post_json(client, url, &trigger(routing_key, notification)).await?;
+            post_json(client, url, &resolve(routing_key, notification)).await
+        }
+    }
+}
+
+fn trigger(routing_key: &str, notification: &Notification) -> Value {
+    let details: Map<String, Value> = notification
+        .facts()
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), Value::String(value)))
+        .collect();
+
+    let links: Vec<Value> = notification
+        .link
+        .iter()
+        .map(|link| json!({ "href": link, "text": "View monitor" }))
+        .collect();
+
+    let severity = match notification.event {
+        NotificationEvent::CertificateExpiring => "warning",
+        _ => "critical",
+    };
+
+    json!({
+        "routing_key": routing_key,
+        "event_action": "trigger",
+        "dedup_key": notification.dedup_key(),
+        "payload": {
+            "summary": notification.summary(),
+            "source": notification.url,
+            "severity": severity,
+            "timestamp": notification.opened_at.to_rfc3339(),
+            "custom_details": details,
+        },
+        "links": links,
+    })
+}
+
+fn resolve(routing_key: &str, notification: &Notification) -> Value {
+    json!({
+        "routing_key": routing_key,
+        "event_action": "resolve",
+        "dedup_key": notification.dedup_key(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::notification;
+    use super::*;
+
+    #[test]
+    fn trigger_and_resolve_share_a_dedup_key() {
+        let down = notification(NotificationEvent::Down, Some(41), Some(7));
+        let mut up = notification(NotificationEvent::Up, Some(41), Some(7));
+        up.resolved_at = Some(up.opened_at);
+
+        let trigger = trigger("routing-key", &down);
+        let resolve = resolve("routing-key", &up);
+
+        assert_eq!(trigger["event_action"], "trigger");
+        assert_eq!(resolve["event_action"], "resolve");
+        assert_eq!(trigger["dedup_key"], "uptime-monitor-incident-41");
+        assert_eq!(trigger["dedup_key"], resolve["dedup_key"]);
+        assert_eq!(trigger["routing_key"], "routing-key");
+    }
+
+    #[test]
+    fn flapping_is_keyed_by_the_monitor_and_certificates_are_warnings() {
+        let flapping = trigger("routing-key", &notification(NotificationEvent::Flapping, None, Some(7)));
+        let stable = resolve("routing-key", &notification(NotificationEvent::Stable, None, Some(7)));
+        let certificate = trigger("routing-key", &notification(NotificationEvent::CertificateExpiring, Some(42), Some(7)));
+
+        assert_eq!(flapping["dedup_key"], "uptime-monitor-flapping-7");
+        assert_eq!(flapping["dedup_key"], stable["dedup_key"]);
+        assert_eq!(flapping["payload"]["severity"], "critical");
+        assert_eq!(certificate["payload"]["severity"], "warning");
+    }
+}
@@ -0,0 +1,26 @@
+use serde_json::{json, Value};
+
+use super::Notification;
+
+/*
+/ a slack incoming webhook message, the details go in a
+/ colour-coded attachment that links back to the monitor
+ */
+pub fn message(notification: &Notification) -> Value {
+    let fields: Vec<Value> = notification
+        .facts()
+        .into_iter()
+        .map(|(name, value)| json!({ "title": name, "value": value, "short": name != "Error" }))
+        .collect();
+
+    json!({
+        "text": notification.title(),
+        "attachments": [{
+            "color": format!("#{:06X}", notification.color()),
+            "title": notification.title(),
+            "title_link": notification.link,
+            "fields": fields,
+            "ts": notification.sent_at.timestamp(),
+        }],
+    })
+}
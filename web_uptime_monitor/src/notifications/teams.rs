@@ -0,0 +1,39 @@
+use serde_json::{json, Value};
+
+use super::Notification;
+
+/*
+/ a message card for a teams incoming webhook connector, with
+/ a button linking back to the monitor when we know its url
+ */
+pub fn message(notification: &Notification) -> Value {
+    let facts: Vec<Value> = notification
+        .facts()
+        .into_iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect();
+
+    let actions: Vec<Value> = notification
+        .link
+        .iter()
+        .map(|link| {
+            json!({
+                "@type": "OpenUri",
+                "name": "View monitor",
+                "targets": [{ "os": "default", "uri": link }],
+            })
+        })
+        .collect();
+
+    json!({
+        "@type": "MessageCard",
+        "@context": "https://schema.org/extensions",
+        "themeColor": format!("{:06X}", notification.color()),
+        "summary": notification.title(),
+        "sections": [{
+            "activityTitle": notification.title(),
+            "facts": facts,
+        }],
+        "potentialAction": actions,
+    })
+}
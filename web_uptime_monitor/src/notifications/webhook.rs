@@ -0,0 +1,61 @@
+use hmac::{Hmac, Mac};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use sha2::Sha256;
+use tokio::time::Duration;
+
+use super::{DeliveryError, Notification};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/*
+/ posts the notification as json. when the channel has a
+/ secret the body is signed with HMAC-SHA256 and the hex
+/ digest sent as X-Signature-256: sha256=<digest>, so the
+/ receiver can check the payload really came from us
+ */
+pub async fn send(
+    client: &Client,
+    url: &str,
+    secret: Option<&str>,
+    notification: &Notification,
+) -> Result<(), DeliveryError> {
+    let body = serde_json::to_vec(notification).map_err(|e| DeliveryError {
+        status: None,
+        message: format!("couldn't serialize notification: {e}"),
+    })?;
+
+    let mut request = client
+        .post(url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .header(CONTENT_TYPE, "application/json")
+        .header("X-Uptime-Event", notification.event.as_str());
+
+    if let Some(secret) = secret {
+        request = request.header("X-Signature-256", format!("sha256={}", sign(secret, &body)));
+    }
+
+    request.body(body).send().await?.error_for_status()?;
+
+    Ok(())
+}
+
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        // test case 2 of RFC 4231
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
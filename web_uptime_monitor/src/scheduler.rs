@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::Utc;
+use reqwest::Url;
+use shuttle_runtime::SecretStore;
+use sqlx::PgPool;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration, Instant, MissedTickBehavior};
+
+use crate::checks::Checker;
+use crate::crypto::SecretCipher;
+use crate::flapping::FlapDetector;
+use crate::maintenance::MaintenanceWindow;
+use crate::notifications::Notifier;
+use crate::Website;
+
+/*
+/ how often the scheduler wakes up to look for websites
+/ that are due, this bounds how late a check can start
+ */
+const SCHEDULER_TICK: Duration = Duration::from_secs(5);
+
+/*
+/ limits for the scheduler, read from Secrets.toml so
+/ they can be tuned per deployment without a rebuild
+ */
+pub struct SchedulerConfig {
+    pub max_concurrent_checks: usize,
+    pub max_checks_per_host: usize,
+    pub flapping_window_mins: usize,
+    pub flapping_state_changes: usize,
+}
+
+impl SchedulerConfig {
+    pub fn from_secrets(secrets: &SecretStore) -> Self {
+        Self {
+            max_concurrent_checks: read_limit(secrets, "MAX_CONCURRENT_CHECKS", 32),
+            max_checks_per_host: read_limit(secrets, "MAX_CHECKS_PER_HOST", 2),
+            flapping_window_mins: read_limit(secrets, "FLAPPING_WINDOW_MINS", 30),
+            flapping_state_changes: read_limit(secrets, "FLAPPING_STATE_CHANGES", 6),
+        }
+    }
+}
+
+fn read_limit(secrets: &SecretStore, key: &str, default: usize) -> usize {
+    secrets
+        .get(key)
+        .and_then(|value| value.parse().ok())
+        .filter(|limit| *limit > 0)
+        .unwrap_or(default)
+}
+
+/*
+/ the scheduler checks every website on its own interval.
+/ checks run concurrently, bounded by a global semaphore and
+/ a semaphore per host so we never hammer a single server
+/ with parallel requests. when a check opens or resolves an
+/ incident the monitor's notification channels are notified,
+/ and unacknowledged incidents are escalated on every tick.
+/ monitors that keep going up and down are marked as flapping
+/ and only notify when they start and stop flapping, and
+/ monitors in a maintenance window don't notify at all
+ */
+pub struct Scheduler {
+    db: PgPool,
+    checker: Checker,
+    notifier: Notifier,
+    flapping: FlapDetector,
+    config: SchedulerConfig,
+    global_permits: Arc<Semaphore>,
+    host_permits: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl Scheduler {
+    pub fn new(db: PgPool, cipher: Option<SecretCipher>, notifier: Notifier, config: SchedulerConfig) -> Self {
+        Self {
+            checker: Checker::new(db.clone(), cipher),
+            notifier,
+            flapping: FlapDetector::new(db.clone(), config.flapping_window_mins, config.flapping_state_changes),
+            db,
+            global_permits: Arc::new(Semaphore::new(config.max_concurrent_checks)),
+            host_permits: Mutex::new(HashMap::new()),
+            config,
+        }
+    }
+
+    /*
+    / main scheduling loop. on every tick we start a check for
+    / each website whose interval has elapsed. if the previous
+    / check of a website is still running when it's due again
+    / we don't start another one on top of it, we record the
+    / skipped tick instead
+     */
+    pub async fn run(self) {
+        let scheduler = Arc::new(self);
+        let mut interval = time::interval(SCHEDULER_TICK);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        let mut next_due: HashMap<i32, Instant> = HashMap::new();
+        let mut in_flight: HashMap<i32, JoinHandle<()>> = HashMap::new();
+
+        loop {
+            interval.tick().await;
+
+            let websites = match sqlx::query_as::<_, Website>("SELECT * FROM websites WHERE paused_at IS NULL")
+                .fetch_all(&scheduler.db)
+                .await {
+                Ok(websites) => websites,
+                Err(e) => {
+                    println!("Failed to fetch websites to check: {e}");
+                    continue;
+                }
+            };
+
+            // forget about websites that have been deleted or paused since the last tick
+            next_due.retain(|id, _| websites.iter().any(|website| website.id == *id));
+            in_flight.retain(|_, handle| !handle.is_finished());
+
+            let now = Instant::now();
+
+            for website in websites {
+                let check_interval = Duration::from_secs(website.check_interval_secs as u64);
+
+                match tick(&mut next_due, website.id, check_interval, in_flight.contains_key(&website.id), now) {
+                    Tick::NotDue => {}
+                    Tick::Skip => scheduler.record_skipped_tick(&website).await,
+                    Tick::Check => {
+                        let id = website.id;
+                        let scheduler = scheduler.clone();
+                        in_flight.insert(id, tokio::spawn(async move {
+                            scheduler.check_with_limits(website).await;
+                        }));
+                    }
+                }
+            }
+
+            scheduler.notifier.escalate().await;
+
+            // drop the semaphores of hosts that no check is holding on to anymore
+            scheduler
+                .host_permits
+                .lock()
+                .await
+                .retain(|_, permits| Arc::strong_count(permits) > 1);
+        }
+    }
+
+    async fn check_with_limits(&self, website: Website) {
+        let _permits = self.acquire_permits(&website.url).await;
+
+        let in_maintenance = match MaintenanceWindow::covers(&self.db, &website, Utc::now()).await {
+            Ok(in_maintenance) => in_maintenance,
+            Err(e) => {
+                println!("Failed to fetch maintenance windows of {}: {e}", website.alias);
+                false
+            }
+        };
+
+        let outcome = self.checker.check(&website).await;
+
+        let transitions = match self.checker.record(&website, &outcome, in_maintenance).await {
+            Ok(transitions) => transitions,
+            Err(e) => {
+                println!("Failed to record check of {}: {e}", website.alias);
+                return;
+            }
+        };
+
+        if in_maintenance {
+            return;
+        }
+
+        // flapping is updated first so the transitions below are held back while it lasts
+        match self.flapping.update(&website).await {
+            Ok(Some(change)) => self.notifier.notify_flapping(&website, &change).await,
+            Ok(None) => {}
+            Err(e) => println!("Failed to update flapping state of {}: {e}", website.alias),
+        }
+
+        for transition in transitions {
+            self.notifier.notify(&website, &transition, outcome.status).await;
+        }
+    }
+
+    /*
+    / wait for the host first, so checks queued behind one slow
+    / host don't hold global slots that checks of every other
+    / host could be using
+     */
+    async fn acquire_permits(&self, url: &str) -> (OwnedSemaphorePermit, OwnedSemaphorePermit) {
+        let host_permit = self
+            .host_permits_for(url)
+            .await
+            .acquire_owned()
+            .await
+            .expect("host semaphore is never closed");
+
+        let global_permit = self
+            .global_permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("global semaphore is never closed");
+
+        (host_permit, global_permit)
+    }
+
+    async fn host_permits_for(&self, url: &str) -> Arc<Semaphore> {
+        self.host_permits
+            .lock()
+            .await
+            .entry(host_key(url))
+            .or_insert_with(|| Arc::new(Semaphore::new(self.config.max_checks_per_host)))
+            .clone()
+    }
+
+    fn checks_in_flight(&self) -> usize {
+        self.config.max_concurrent_checks - self.global_permits.available_permits()
+    }
+
+    async fn record_skipped_tick(&self, website: &Website) {
+        let in_flight = self.checks_in_flight();
+
+        println!(
+            "Previous check of {} still running ({in_flight} checks in flight), skipping tick",
+            website.alias
+        );
+
+        if let Err(e) = sqlx::query(
+            "INSERT INTO scheduler_skipped_ticks (website_id, checks_in_flight) VALUES ($1, $2)"
+        )
+            .bind(website.id)
+            .bind(in_flight as i32)
+            .execute(&self.db)
+            .await {
+            println!("Failed to record skipped tick: {e}");
+        }
+    }
+}
+
+/*
+/ what the scheduler does about a website on a tick
+ */
+#[derive(Debug, PartialEq)]
+enum Tick {
+    NotDue,
+    Check,
+    Skip,
+}
+
+/*
+/ a website is due once its interval has passed since it was
+/ last due, whether it was checked then or skipped because its
+/ previous check was still running
+ */
+fn tick(next_due: &mut HashMap<i32, Instant>, website_id: i32, check_interval: Duration, in_flight: bool, now: Instant)
+    -> Tick {
+    if next_due.get(&website_id).is_some_and(|due| *due > now) {
+        return Tick::NotDue;
+    }
+
+    next_due.insert(website_id, now + check_interval);
+
+    if in_flight {
+        Tick::Skip
+    } else {
+        Tick::Check
+    }
+}
+
+/*
+/ checks are limited per host, urls that can't be parsed
+/ are limited on their own
+ */
+fn host_key(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_owned))
+        .unwrap_or_else(|| url.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler(max_concurrent_checks: usize, max_checks_per_host: usize) -> Scheduler {
+        let db = PgPool::connect_lazy("postgres://localhost/uptime").unwrap();
+        let config = SchedulerConfig {
+            max_concurrent_checks,
+            max_checks_per_host,
+            flapping_window_mins: 30,
+            flapping_state_changes: 6,
+        };
+
+        Scheduler::new(db.clone(), None, Notifier::new(db, None, None, None), config)
+    }
+
+    #[test]
+    fn checks_due_websites_and_skips_ones_still_running() {
+        let mut next_due = HashMap::new();
+        let interval = Duration::from_secs(60);
+        let start = Instant::now();
+
+        assert_eq!(tick(&mut next_due, 1, interval, false, start), Tick::Check);
+        assert_eq!(tick(&mut next_due, 1, interval, true, start + Duration::from_secs(5)), Tick::NotDue);
+        assert_eq!(tick(&mut next_due, 1, interval, true, start + Duration::from_secs(60)), Tick::Skip);
+        // a skipped tick is only recorded once per interval
+        assert_eq!(tick(&mut next_due, 1, interval, true, start + Duration::from_secs(65)), Tick::NotDue);
+        assert_eq!(tick(&mut next_due, 1, interval, false, start + Duration::from_secs(120)), Tick::Check);
+
+        assert_eq!(tick(&mut next_due, 2, interval, false, start + Duration::from_secs(65)), Tick::Check);
+    }
+
+    #[test]
+    fn limits_checks_by_host_name() {
+        assert_eq!(host_key("https://example.com/health"), "example.com");
+        assert_eq!(host_key("https://example.com:8443/other"), "example.com");
+        assert_eq!(host_key("tcp://db.example.com:5432"), "db.example.com");
+        assert_eq!(host_key("not a url"), "not a url");
+    }
+
+    #[tokio::test]
+    async fn waits_for_the_host_and_global_limits() {
+        let scheduler = scheduler(2, 1);
+        let wait = Duration::from_millis(50);
+
+        let first = scheduler.acquire_permits("https://a.example.com/health").await;
+        assert!(time::timeout(wait, scheduler.acquire_permits("https://a.example.com/other")).await.is_err());
+
+        let second = scheduler.acquire_permits("https://b.example.com").await;
+        assert_eq!(scheduler.checks_in_flight(), 2);
+        assert!(time::timeout(wait, scheduler.acquire_permits("https://c.example.com")).await.is_err());
+
+        drop(first);
+        let _third = time::timeout(wait, scheduler.acquire_permits("https://a.example.com/other")).await.unwrap();
+        assert_eq!(scheduler.checks_in_flight(), 2);
+
+        drop(second);
+        assert_eq!(scheduler.checks_in_flight(), 1);
+    }
+}